@@ -0,0 +1,198 @@
+// lib.rs
+//
+// (c) 2017 James Crooks
+//
+// `#[derive(AvroCodec)]`: generates `AvroCodec` impls for structs and
+// enums so application types don't need hand-written `encode`/`decode`,
+// the same way `bincode_derive`/`bitcode_derive` cover their codecs.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use syn::{Data, DeriveInput, Fields, GenericParam, Generics};
+
+#[proc_macro_derive(AvroCodec)]
+pub fn derive_avro_codec(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("AvroCodec derive: failed to parse item");
+
+    let name = &input.ident;
+    let generics = add_trait_bounds(input.generics.clone());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match input.data {
+        Data::Struct(ref data) => derive_struct(&data.fields),
+        Data::Enum(ref data) => derive_enum(name, data),
+        Data::Union(_) => panic!("AvroCodec cannot be derived for unions"),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::avro_rs::codec::AvroCodec for #name #ty_generics #where_clause {
+            #body
+        }
+    };
+
+    expanded.into()
+}
+
+/// Every generic type parameter must itself implement `AvroCodec`,
+/// since each field's `encode`/`decode` is delegated straight to it.
+fn add_trait_bounds(mut generics: Generics) -> Generics {
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(syn::parse_quote!(::avro_rs::codec::AvroCodec));
+        }
+    }
+    generics
+}
+
+fn derive_struct(fields: &Fields) -> proc_macro2::TokenStream {
+    match *fields {
+        Fields::Named(ref named) => {
+            let field_names: Vec<_> = named.named.iter()
+                .map(|field| field.ident.clone().unwrap())
+                .collect();
+
+            quote! {
+                fn encode(&self, writer: &mut ::avro_rs::io::Writer) {
+                    #( ::avro_rs::codec::AvroCodec::encode(&self.#field_names, writer); )*
+                }
+
+                fn decode(reader: &mut ::avro_rs::io::Reader) -> Option<Self> {
+                    #(
+                        let #field_names = match ::avro_rs::codec::AvroCodec::decode(reader) {
+                            Some(value) => value,
+                            None => return None,
+                        };
+                    )*
+                    Some(Self { #( #field_names ),* })
+                }
+            }
+        }
+        Fields::Unnamed(ref unnamed) => {
+            // `syn::Index`, not a raw `usize` - interpolating a `usize`
+            // into `self.#i` produces a suffixed literal (`self.0usize`),
+            // which is invalid syntax for a tuple index.
+            let indices: Vec<_> = (0..unnamed.unnamed.len())
+                .map(|i| syn::Index::from(i))
+                .collect();
+            let bindings: Vec<_> = (0..unnamed.unnamed.len())
+                .map(|i| syn::Ident::new(&format!("field_{}", i), proc_macro2::Span::call_site()))
+                .collect();
+
+            quote! {
+                fn encode(&self, writer: &mut ::avro_rs::io::Writer) {
+                    #( ::avro_rs::codec::AvroCodec::encode(&self.#indices, writer); )*
+                }
+
+                fn decode(reader: &mut ::avro_rs::io::Reader) -> Option<Self> {
+                    #(
+                        let #bindings = match ::avro_rs::codec::AvroCodec::decode(reader) {
+                            Some(value) => value,
+                            None => return None,
+                        };
+                    )*
+                    Some(Self( #( #bindings ),* ))
+                }
+            }
+        }
+        Fields::Unit => {
+            quote! {
+                fn encode(&self, _writer: &mut ::avro_rs::io::Writer) {}
+
+                fn decode(_reader: &mut ::avro_rs::io::Reader) -> Option<Self> {
+                    Some(Self)
+                }
+            }
+        }
+    }
+}
+
+fn derive_enum(name: &syn::Ident, data: &syn::DataEnum) -> proc_macro2::TokenStream {
+    let mut encode_arms = Vec::new();
+    let mut decode_arms = Vec::new();
+
+    for (index, variant) in data.variants.iter().enumerate() {
+        let index = index as i32;
+        let variant_name = &variant.ident;
+
+        match variant.fields {
+            Fields::Unit => {
+                encode_arms.push(quote! {
+                    #name::#variant_name => ::avro_rs::codec::AvroCodec::encode(&(#index), writer)
+                });
+                decode_arms.push(quote! {
+                    #index => Some(#name::#variant_name)
+                });
+            }
+            Fields::Unnamed(ref unnamed) => {
+                let bindings: Vec<_> = (0..unnamed.unnamed.len())
+                    .map(|i| syn::Ident::new(&format!("field_{}", i), proc_macro2::Span::call_site()))
+                    .collect();
+
+                encode_arms.push(quote! {
+                    #name::#variant_name( #( ref #bindings ),* ) => {
+                        ::avro_rs::codec::AvroCodec::encode(&(#index), writer);
+                        #( ::avro_rs::codec::AvroCodec::encode(#bindings, writer); )*
+                    }
+                });
+                decode_arms.push(quote! {
+                    #index => {
+                        #(
+                            let #bindings = match ::avro_rs::codec::AvroCodec::decode(reader) {
+                                Some(value) => value,
+                                None => return None,
+                            };
+                        )*
+                        Some(#name::#variant_name( #( #bindings ),* ))
+                    }
+                });
+            }
+            Fields::Named(ref named) => {
+                let field_names: Vec<_> = named.named.iter()
+                    .map(|field| field.ident.clone().unwrap())
+                    .collect();
+
+                encode_arms.push(quote! {
+                    #name::#variant_name { #( ref #field_names ),* } => {
+                        ::avro_rs::codec::AvroCodec::encode(&(#index), writer);
+                        #( ::avro_rs::codec::AvroCodec::encode(#field_names, writer); )*
+                    }
+                });
+                decode_arms.push(quote! {
+                    #index => {
+                        #(
+                            let #field_names = match ::avro_rs::codec::AvroCodec::decode(reader) {
+                                Some(value) => value,
+                                None => return None,
+                            };
+                        )*
+                        Some(#name::#variant_name { #( #field_names ),* })
+                    }
+                });
+            }
+        }
+    }
+
+    quote! {
+        fn encode(&self, writer: &mut ::avro_rs::io::Writer) {
+            match *self {
+                #( #encode_arms ),*
+            }
+        }
+
+        fn decode(reader: &mut ::avro_rs::io::Reader) -> Option<Self> {
+            let index: i32 = match ::avro_rs::codec::AvroCodec::decode(reader) {
+                Some(value) => value,
+                None => return None,
+            };
+            match index {
+                #( #decode_arms, )*
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// derive.rs
+//
+// Integration tests for `#[derive(AvroCodec)]`. These live in
+// `tests/`, not `src/`, since the macro's whole point is generating
+// code in a *consuming* crate - the only way to catch a generated
+// snippet that doesn't compile (or resolves differently than the
+// hand-written impls in `avro_rs::codec`) is to actually derive it
+// on a type here and exercise the result.
+
+extern crate avro_derive;
+extern crate avro_rs;
+
+use avro_derive::AvroCodec;
+use avro_rs::codec;
+use avro_rs::io::{Reader, Writer};
+
+// Deliberately does not `use avro_rs::codec::AvroCodec` at module
+// scope - the derive's generated `encode`/`decode` bodies must fully
+// qualify every call themselves, not lean on a bare method call that
+// only resolves if the consuming module happens to import the trait.
+
+#[derive(AvroCodec)]
+struct NamedFields {
+    count: i32,
+    label: String,
+}
+
+#[derive(AvroCodec)]
+struct TupleStruct(i32, i32);
+
+#[derive(AvroCodec)]
+struct UnitStruct;
+
+#[derive(AvroCodec)]
+struct GenericWrapper<T: codec::AvroCodec> {
+    value: T,
+}
+
+#[derive(AvroCodec)]
+enum Shape {
+    Point,
+    Circle(i32),
+    Rectangle { width: i32, height: i32 },
+}
+
+fn roundtrip<T: codec::AvroCodec>(value: &T) -> T {
+    let mut writer = Writer::new();
+    value.encode(&mut writer);
+    let bytes = writer.into_bytes();
+    let mut reader = Reader::new(&bytes);
+    T::decode(&mut reader).unwrap()
+}
+
+#[test]
+fn test_named_struct_roundtrip() {
+    let value = NamedFields { count: 3, label: String::from("three") };
+    let decoded = roundtrip(&value);
+    assert_eq!(decoded.count, 3);
+    assert_eq!(decoded.label, "three");
+}
+
+#[test]
+fn test_tuple_struct_roundtrip() {
+    let value = TupleStruct(5, -5);
+    let decoded = roundtrip(&value);
+    assert_eq!(decoded.0, 5);
+    assert_eq!(decoded.1, -5);
+}
+
+#[test]
+fn test_unit_struct_roundtrip() {
+    let bytes = {
+        let mut writer = Writer::new();
+        codec::AvroCodec::encode(&UnitStruct, &mut writer);
+        writer.into_bytes()
+    };
+    assert!(bytes.is_empty());
+    let mut reader = Reader::new(&bytes);
+    <UnitStruct as codec::AvroCodec>::decode(&mut reader).unwrap();
+}
+
+#[test]
+fn test_generic_struct_roundtrip() {
+    let value = GenericWrapper { value: 42i32 };
+    let decoded = roundtrip(&value);
+    assert_eq!(decoded.value, 42);
+}
+
+#[test]
+fn test_enum_variants_roundtrip() {
+    match roundtrip(&Shape::Point) {
+        Shape::Point => (),
+        _ => panic!("expected Shape::Point"),
+    }
+
+    match roundtrip(&Shape::Circle(7)) {
+        Shape::Circle(radius) => assert_eq!(radius, 7),
+        _ => panic!("expected Shape::Circle"),
+    }
+
+    match roundtrip(&Shape::Rectangle { width: 2, height: 3 }) {
+        Shape::Rectangle { width, height } => {
+            assert_eq!(width, 2);
+            assert_eq!(height, 3);
+        }
+        _ => panic!("expected Shape::Rectangle"),
+    }
+}
+
+#[test]
+fn test_enum_decode_rejects_unknown_variant_index() {
+    let mut writer = Writer::new();
+    codec::AvroCodec::encode(&99i32, &mut writer);
+    let bytes = writer.into_bytes();
+    let mut reader = Reader::new(&bytes);
+    assert!(<Shape as codec::AvroCodec>::decode(&mut reader).is_none());
+}
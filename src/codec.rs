@@ -5,47 +5,48 @@
 // (as HashMap<String, T>)
 // (c) 2016 James Crooks
 
-use std::iter::Iterator;
 use std::collections::HashMap;
 use std::mem;
 
-pub type ByteStream = Iterator<Item = u8>;
+use super::io::{Reader, Writer};
 
 pub trait AvroCodec: Sized {
-    fn encode(&self) -> Vec<u8>;
-    fn decode(&mut ByteStream) -> Option<Self>;
+    fn encode(&self, writer: &mut Writer);
+    fn decode(reader: &mut Reader) -> Option<Self>;
+}
+
+/// Borrowed-decode counterpart to `AvroCodec`. A handful of types
+/// (`Bytes`, `String`) can be read back as a view straight into the
+/// input buffer rather than an owned copy, so this is a separate trait
+/// instead of a mode on `AvroCodec::decode`.
+pub trait AvroCodecBorrowed<'a>: Sized {
+    fn decode_borrowed(reader: &mut Reader<'a>) -> Option<Self>;
 }
 
 impl AvroCodec for i32 {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         if *self == 0 {
-            return vec![0u8];
+            writer.write_byte(0u8);
+            return;
         }
 
         let mut vint = ((*self << 1) ^ (*self >> 31)) as u32;
 
-        let mut encoded = Vec::new();
-
-        while vint != 0 {
-            let byte = (vint | 0x80) as u8;
-            encoded.push(byte);
+        while vint >= 0x80 {
+            writer.write_byte((vint | 0x80) as u8);
             vint = vint >> 7;
         }
 
-        if let Some(last) = encoded.pop() {
-            encoded.push(last ^ 0x80);
-        }
-
-        encoded
+        writer.write_byte(vint as u8);
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<Self> {
+    fn decode(reader: &mut Reader) -> Option<Self> {
         let mut vint: u32 = 0;
         let mut count = 0;
         loop {
-            if let Some(byte) = bytes.next() {
+            if let Some(byte) = reader.next() {
                 vint = vint | (((byte & 0x7F) as u32) << (7 * count));
                 count += 1;
                 if byte & 0x80 == 0 {
@@ -66,33 +67,27 @@ impl AvroCodec for i32 {
 }
 
 impl AvroCodec for i64 {
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         if *self == 0 {
-            return vec![0u8];
+            writer.write_byte(0u8);
+            return;
         }
 
         let mut vint = ((*self << 1) ^ (*self >> 63)) as u64;
 
-        let mut encoded = Vec::new();
-
-        while vint != 0 {
-            let byte = (vint | 0x80) as u8;
-            encoded.push(byte);
+        while vint >= 0x80 {
+            writer.write_byte((vint | 0x80) as u8);
             vint = vint >> 7;
         }
 
-        if let Some(last) = encoded.pop() {
-            encoded.push(last ^ 0x80);
-        }
-
-        encoded
+        writer.write_byte(vint as u8);
     }
 
-    fn decode(bytes: &mut ByteStream) -> Option<i64> {
+    fn decode(reader: &mut Reader) -> Option<i64> {
         let mut vint: u64 = 0;
         let mut count = 0;
         loop {
-            if let Some(byte) = bytes.next() {
+            if let Some(byte) = reader.next() {
                 vint = vint | (((byte & 0x7F) as u64) << (7 * count));
                 count += 1;
                 if byte & 0x80 == 0 {
@@ -114,153 +109,142 @@ impl AvroCodec for i64 {
 
 impl AvroCodec for usize {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         if *self == 0 {
-            return vec![0u8];
+            writer.write_byte(0u8);
+            return;
         }
+
         let mut vint = *self << 1; // This drops the MSB. Better keep collections under 2^63 items!
-        let mut encoded = Vec::new();
 
-        while vint != 0 {
-            let byte = (vint | 0x80) as u8;
-            encoded.push(byte);
+        while vint >= 0x80 {
+            writer.write_byte((vint | 0x80) as u8);
             vint = vint >> 7;
         }
 
-        if let Some(last) = encoded.pop() {
-            encoded.push(last ^ 0x80);
-        }
-
-        encoded
+        writer.write_byte(vint as u8);
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<Self> {
-        i32::decode(bytes).map(|x| x.abs() as usize)
+    fn decode(reader: &mut Reader) -> Option<Self> {
+        // `i32::MIN.abs()` overflows and panics, and untrusted input
+        // (e.g. an Object Container File's block lengths) can encode
+        // exactly that zig-zag value, so this has to fail gracefully
+        // via `checked_abs` rather than trust the bytes fit.
+        i32::decode(reader).and_then(|x| x.checked_abs()).map(|x| x as usize)
     }
 }
 
 impl AvroCodec for f32 {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         // Unsafe for performance
         // This use is safe, since we are just turning a 4-byte
         // object into an array of exactly 4 bytes
-        unsafe { mem::transmute::<f32, [u8; 4]>(*self).to_vec() }
+        writer.write_bytes(&unsafe { mem::transmute::<f32, [u8; 4]>(*self) });
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<f32> {
-        match (bytes.next(), bytes.next(), bytes.next(), bytes.next()) {
-            (Some(b1), Some(b2), Some(b3), Some(b4)) => 
-                Some(unsafe { mem::transmute::<[u8; 4], f32>([b1, b2, b3, b4]) }),
-            _ => None
-        }
+    fn decode(reader: &mut Reader) -> Option<f32> {
+        reader.take(4).map(|bytes| {
+            let mut array = [0u8; 4];
+            array.copy_from_slice(bytes);
+            unsafe { mem::transmute::<[u8; 4], f32>(array) }
+        })
     }
 }
 
 impl AvroCodec for f64 {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         // Unsafe for performance
         // This use is safe, since we are just turning an 8-byte
         // object into an array of exactly 8 bytes
-        unsafe { mem::transmute::<f64, [u8; 8]>(*self).to_vec() }
+        writer.write_bytes(&unsafe { mem::transmute::<f64, [u8; 8]>(*self) });
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<f64> {
-        match (bytes.next(), bytes.next(), bytes.next(), bytes.next(),
-               bytes.next(), bytes.next(), bytes.next(), bytes.next()) {
-            (Some(b1), Some(b2), Some(b3), Some(b4),
-             Some(b5), Some(b6), Some(b7), Some(b8)) => {
-                Some(unsafe 
-                     { mem::transmute::<[u8; 8], f64>([b1, b2, b3, b4, b5, b6, b7, b8]) 
-                     })
-            },
-            _ => None
-        }
+    fn decode(reader: &mut Reader) -> Option<f64> {
+        reader.take(8).map(|bytes| {
+            let mut array = [0u8; 8];
+            array.copy_from_slice(bytes);
+            unsafe { mem::transmute::<[u8; 8], f64>(array) }
+        })
     }
 }
 
 impl AvroCodec for String {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
-        let mut bytes = self.as_bytes().to_vec();
-        let mut len = bytes.len().encode();
-        let mut encoded: Vec<u8> = Vec::with_capacity(bytes.len() + len.len());
-        encoded.append(&mut len);
-        encoded.append(&mut bytes);
-        encoded
+    fn encode(&self, writer: &mut Writer) {
+        self.len().encode(writer);
+        writer.write_bytes(self.as_bytes());
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<Self> {
-        let len = if let Some(len) = usize::decode(bytes) {
-            len
-        } else { 
-            return None;
-        };
-
-        let strdata: Vec<u8> = bytes.take(len).collect();
-        if strdata.len() < len {
-            return None;
-        }
+    fn decode(reader: &mut Reader) -> Option<Self> {
+        let len = usize::decode(reader)?;
+        reader.take(len).and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+    }
+}
 
-        String::from_utf8(strdata).ok()
+impl<'a> AvroCodecBorrowed<'a> for &'a str {
+    #[inline]
+    fn decode_borrowed(reader: &mut Reader<'a>) -> Option<Self> {
+        let len = usize::decode(reader)?;
+        reader.take(len).and_then(|bytes| ::std::str::from_utf8(bytes).ok())
+    }
+}
+
+impl<'a> AvroCodecBorrowed<'a> for &'a [u8] {
+    #[inline]
+    fn decode_borrowed(reader: &mut Reader<'a>) -> Option<Self> {
+        let len = usize::decode(reader)?;
+        reader.take(len)
     }
 }
 
 impl AvroCodec for bool {
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         match *self {
-            true => vec![0x1],
-            false => vec![0x0]
+            true => writer.write_byte(0x1),
+            false => writer.write_byte(0x0),
         }
     }
 
-    fn decode(bytes: &mut ByteStream) -> Option<bool> {
-        match bytes.next() {
+    fn decode(reader: &mut Reader) -> Option<bool> {
+        match reader.next() {
             Some(0u8) => Some(false),
             Some(1u8) => Some(true),
-            _ => None
+            _ => None,
         }
     }
 }
 
 impl AvroCodec for u8 {
-    fn encode(&self) -> Vec<u8> {
-        vec![*self]
+    fn encode(&self, writer: &mut Writer) {
+        writer.write_byte(*self);
     }
 
-    fn decode(bytes: &mut ByteStream) -> Option<u8> {
-        bytes.next()
+    fn decode(reader: &mut Reader) -> Option<u8> {
+        reader.next()
     }
 }
 
 impl<T> AvroCodec for Vec<T> where T: AvroCodec {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
-        let mut encoded = Vec::new();
-        encoded.append(&mut self.len().encode());
-        self.iter().fold(encoded, |mut acc, item| {
-            let mut encoded = item.encode();
-            acc.append(&mut encoded);
-            acc
-        })
+    fn encode(&self, writer: &mut Writer) {
+        self.len().encode(writer);
+        for item in self.iter() {
+            item.encode(writer);
+        }
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<Self> {
-        let mut len = if let Some(len) = usize::decode(bytes) {
-            len
-        } else {
-            return None;
-        };
-
+    fn decode(reader: &mut Reader) -> Option<Self> {
+        let mut len = usize::decode(reader)?;
         let mut ret = Vec::with_capacity(len);
         while len > 0 {
-            if let Some(elem) = T::decode(bytes) {
+            if let Some(elem) = T::decode(reader) {
                 ret.push(elem);
             } else {
                 return None;
@@ -274,205 +258,207 @@ impl<T> AvroCodec for Vec<T> where T: AvroCodec {
 
 impl <T> AvroCodec for HashMap<String, T> where T: AvroCodec {
     #[inline]
-    fn encode(&self) -> Vec<u8> {
+    fn encode(&self, writer: &mut Writer) {
         if self.is_empty() {
-            return vec![0x0];
+            writer.write_byte(0x0);
+            return;
         }
 
-        let mut encoded = Vec::new();
-        encoded.append(&mut self.len().encode());
+        self.len().encode(writer);
         for (key, value) in self.iter() {
-            encoded.append(&mut key.encode());
-            encoded.append(&mut value.encode());
+            key.encode(writer);
+            value.encode(writer);
         }
-        encoded.push(0x0u8);
-        encoded
+        writer.write_byte(0x0);
     }
 
     #[inline]
-    fn decode(bytes: &mut ByteStream) -> Option<Self> {
-        let mut len = match usize::decode(bytes) {
-            Some(0) => return Some(HashMap::new()),
-            Some(len) => len,
-            None => return None,
-        };
-
-        let mut decoded = HashMap::with_capacity(len);
-        while len > 0 {
-            match (String::decode(bytes), T::decode(bytes)) {
-                (Some(key), Some(value)) => decoded.insert(key, value),
-                _ => return None,
-            };
-            len -= 1;
+    fn decode(reader: &mut Reader) -> Option<Self> {
+        // Maps are Avro "blocked" data: a count-prefixed run of entries,
+        // repeated until a block of count zero terminates the sequence
+        // (which `encode` always writes, even after a single block).
+        let mut decoded = HashMap::new();
+        loop {
+            let len = usize::decode(reader)?;
+            if len == 0 {
+                break;
+            }
+
+            for _ in 0..len {
+                match (String::decode(reader), T::decode(reader)) {
+                    (Some(key), Some(value)) => decoded.insert(key, value),
+                    _ => return None,
+                };
+            }
         }
 
         Some(decoded)
     }
 }
 
+/// Convenience wrapper for the common case of encoding a single value
+/// to an owned `Vec<u8>` instead of threading a `Writer` through by hand.
+pub fn encode<T: AvroCodec>(value: &T) -> Vec<u8> {
+    let mut writer = Writer::new();
+    value.encode(&mut writer);
+    writer.into_bytes()
+}
+
+/// Convenience wrapper for decoding a single value out of a byte slice.
+pub fn decode<T: AvroCodec>(bytes: &[u8]) -> Option<T> {
+    let mut reader = Reader::new(bytes);
+    T::decode(&mut reader)
+}
+
 #[cfg(test)]
 mod tests {
-    pub use super::AvroCodec;
+    pub use super::{AvroCodec, AvroCodecBorrowed, decode, encode};
     use std::{f32, f64};
     use std::collections::HashMap;
+    use super::super::io::Reader;
 
     #[test]
     fn test_i32_codec() {
-        assert_eq!(0i32.encode(), vec![0u8]);
-        assert_eq!(1i32.encode(), vec![2u8]);
-        assert_eq!((-1i32).encode(), vec![1u8]);
-        assert_eq!(i32::max_value().encode(),
+        assert_eq!(encode(&0i32), vec![0u8]);
+        assert_eq!(encode(&1i32), vec![2u8]);
+        assert_eq!(encode(&(-1i32)), vec![1u8]);
+        assert_eq!(encode(&i32::max_value()),
                    vec![0xFE, 0xFF, 0xFF, 0xFF, 0x0F]);
-        assert_eq!(i32::min_value().encode(),
+        assert_eq!(encode(&i32::min_value()),
                    vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
         assert_eq!(i32::max_value(),
-                i32::decode(&mut i32::max_value()
-                            .encode()
-                            .into_iter()).unwrap());
+                   decode::<i32>(&encode(&i32::max_value())).unwrap());
         assert_eq!(i32::min_value(),
-                i32::decode(&mut i32::min_value()
-                            .encode()
-                            .into_iter()).unwrap());
+                   decode::<i32>(&encode(&i32::min_value())).unwrap());
     }
 
     #[test]
     fn test_i64_codec() {
-        assert_eq!(0i64.encode(), vec![0u8]);
-        assert_eq!(1i64.encode(), vec![2u8]);
-        assert_eq!((-1i64).encode(), vec![1u8]);
-        assert_eq!(i64::max_value().encode(),
-                vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF,
-                     0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
-        assert_eq!(i64::min_value().encode(),
-                vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                     0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+        assert_eq!(encode(&0i64), vec![0u8]);
+        assert_eq!(encode(&1i64), vec![2u8]);
+        assert_eq!(encode(&(-1i64)), vec![1u8]);
+        assert_eq!(encode(&i64::max_value()),
+                   vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF,
+                        0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+        assert_eq!(encode(&i64::min_value()),
+                   vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                        0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
         assert_eq!(i64::max_value(),
-                i64::decode(&mut i64::max_value()
-                            .encode()
-                            .into_iter()).unwrap());
+                   decode::<i64>(&encode(&i64::max_value())).unwrap());
         assert_eq!(i64::min_value(),
-                i64::decode(&mut i64::min_value()
-                            .encode()
-                            .into_iter()).unwrap());
+                   decode::<i64>(&encode(&i64::min_value())).unwrap());
     }
 
     #[test]
     fn test_usize_codec() {
-        assert_eq!(2usize.encode(), vec![4u8]);
-        assert_eq!(2usize, usize::decode(&mut vec![4u8]
-                                         .into_iter()).unwrap());
-        assert_eq!(2usize, usize::decode(&mut vec![3u8]
-                                         .into_iter()).unwrap());
+        assert_eq!(encode(&2usize), vec![4u8]);
+        assert_eq!(2usize, decode::<usize>(&[4u8]).unwrap());
+        assert_eq!(2usize, decode::<usize>(&[3u8]).unwrap());
+    }
+
+    #[test]
+    fn test_usize_decode_rejects_i32_min_instead_of_panicking() {
+        // The zig-zag encoding of i32::MIN decodes to a negative i32
+        // whose `abs()` overflows - this must fail gracefully, not panic.
+        let bytes = encode(&i32::min_value());
+        assert_eq!(None, decode::<usize>(&bytes));
     }
 
     #[test]
     fn test_f32_codec() {
-        assert_eq!(0f32, f32::decode(&mut 0f32.encode()
-                                     .into_iter()).unwrap());
-        assert_eq!(f32::MIN,
-                f32::decode(&mut f32::MIN.encode()
-                            .into_iter()).unwrap());
-        assert_eq!(f32::MAX,
-                f32::decode(&mut f32::MAX.encode()
-                            .into_iter()).unwrap());
+        assert_eq!(0f32, decode::<f32>(&encode(&0f32)).unwrap());
+        assert_eq!(f32::MIN, decode::<f32>(&encode(&f32::MIN)).unwrap());
+        assert_eq!(f32::MAX, decode::<f32>(&encode(&f32::MAX)).unwrap());
     }
 
     #[test]
     fn test_f64_codec() {
-        assert_eq!(0f64, f64::decode(&mut 0f64.encode()
-                                     .into_iter()).unwrap());
-        assert_eq!(f64::MIN,
-                   f64::decode(&mut f64::MIN.encode()
-                               .into_iter()).unwrap());
-        assert_eq!(f64::MAX,
-                   f64::decode(&mut f64::MAX.encode()
-                               .into_iter()).unwrap());
+        assert_eq!(0f64, decode::<f64>(&encode(&0f64)).unwrap());
+        assert_eq!(f64::MIN, decode::<f64>(&encode(&f64::MIN)).unwrap());
+        assert_eq!(f64::MAX, decode::<f64>(&encode(&f64::MAX)).unwrap());
     }
 
     #[test]
     fn test_vec_i32_codec() {
-        assert_eq!(Vec::<i32>::new().encode(), vec![0x0]);
-        assert_eq!(vec![2i32].encode(), vec![0x2, 0x4]);
-        assert_eq!(Vec::<i32>::decode(&mut vec![0x2, 0x4]
-                                      .into_iter()).unwrap(), 
-                   vec![2i32]);
-        assert_eq!(Vec::<i32>::decode(&mut vec![0x0]
-                                      .into_iter()).unwrap(), 
-                   vec![]);
-        assert_eq!(Vec::<i32>::decode(&mut vec![0x1, 0x1]
-                                      .into_iter()).unwrap(), 
-                   vec![-1i32]);
+        assert_eq!(encode(&Vec::<i32>::new()), vec![0x0]);
+        assert_eq!(encode(&vec![2i32]), vec![0x2, 0x4]);
+        assert_eq!(decode::<Vec<i32>>(&[0x2, 0x4]).unwrap(), vec![2i32]);
+        assert_eq!(decode::<Vec<i32>>(&[0x0]).unwrap(), vec![]);
+        assert_eq!(decode::<Vec<i32>>(&[0x1, 0x1]).unwrap(), vec![-1i32]);
     }
 
     #[test]
     fn test_vec_f32_codec() {
-        assert_eq!(Vec::<f32>::new().encode(), vec![0x0]);
-        assert_eq!(vec![0f32, f32::MAX, f32::MIN],
-                   Vec::<f32>::decode(&mut vec![0f32, f32::MAX, f32::MIN]
-                                      .encode()
-                                      .into_iter()).unwrap())
+        assert_eq!(encode(&Vec::<f32>::new()), vec![0x0]);
+        let values = vec![0f32, f32::MAX, f32::MIN];
+        assert_eq!(values, decode::<Vec<f32>>(&encode(&values)).unwrap());
     }
 
     #[test]
     fn test_string_codec() {
-        assert_eq!(String::from("abcde").encode(), 
+        assert_eq!(encode(&String::from("abcde")),
                    vec![0x0A, 0x61, 0x62, 0x63, 0x64, 0x65]);
         assert_eq!(String::from("abcde"),
-        String::decode(&mut vec![0x0A, 0x61, 0x62, 0x63, 0x64, 0x65]
-                       .into_iter()).unwrap());
-        assert_eq!(String::from(""), 
-                   String::decode(&mut vec![0x0].into_iter()).unwrap());
+                   decode::<String>(&[0x0A, 0x61, 0x62, 0x63, 0x64, 0x65]).unwrap());
+        assert_eq!(String::from(""), decode::<String>(&[0x0]).unwrap());
+    }
+
+    #[test]
+    fn test_string_borrowed_decode_is_zero_copy() {
+        let encoded = encode(&String::from("abcde"));
+        let mut reader = Reader::new(&encoded);
+        let borrowed = <&str>::decode_borrowed(&mut reader).unwrap();
+        assert_eq!(borrowed, "abcde");
+    }
+
+    #[test]
+    fn test_bytes_borrowed_decode_is_zero_copy() {
+        let encoded = encode(&vec![0xAAu8, 0xBB, 0xCC]);
+        let mut reader = Reader::new(&encoded);
+        let borrowed = <&[u8]>::decode_borrowed(&mut reader).unwrap();
+        assert_eq!(borrowed, &[0xAAu8, 0xBB, 0xCC][..]);
     }
 
     #[test]
     fn test_vec_string_codec() {
-        assert_eq!(Vec::<String>::new().encode(), vec![0x0]);
-        assert_eq!(vec![String::from("This"), String::from("is"), 
-                   String::from("a"), String::from("test.")],
-                   Vec::<String>::decode(&mut vec![
-                                         String::from("This"),
-                                         String::from("is"),
-                                         String::from("a"),
-                                         String::from("test.")]
-                                         .encode()
-                                         .into_iter()).unwrap());
+        assert_eq!(encode(&Vec::<String>::new()), vec![0x0]);
+        let values = vec![String::from("This"), String::from("is"),
+                           String::from("a"), String::from("test.")];
+        assert_eq!(values, decode::<Vec<String>>(&encode(&values)).unwrap());
     }
 
     #[test]
     fn test_bool_codec() {
-        assert_eq!(true.encode(), vec![0x1]);
-        assert_eq!(false.encode(), vec![0x0]);
-        assert_eq!(true, bool::decode(&mut vec![0x1].into_iter()).unwrap());
-        assert_eq!(false, bool::decode(&mut vec![0x0].into_iter()).unwrap());
-        assert_eq!(None, bool::decode(&mut vec![0x2].into_iter()));
+        assert_eq!(encode(&true), vec![0x1]);
+        assert_eq!(encode(&false), vec![0x0]);
+        assert_eq!(true, decode::<bool>(&[0x1]).unwrap());
+        assert_eq!(false, decode::<bool>(&[0x0]).unwrap());
+        assert_eq!(None, decode::<bool>(&[0x2]));
     }
 
     #[test]
     fn test_byte_codec() {
-        assert_eq!(0xFFu8.encode(), vec![0xFFu8]);
-        assert_eq!(0xFFu8, u8::decode(&mut vec![0xFFu8].into_iter()).unwrap());
-        assert_eq!(0xFFu8, u8::decode(&mut 0xFFu8.encode().into_iter()).unwrap());
+        assert_eq!(encode(&0xFFu8), vec![0xFFu8]);
+        assert_eq!(0xFFu8, decode::<u8>(&[0xFFu8]).unwrap());
+        assert_eq!(0xFFu8, decode::<u8>(&encode(&0xFFu8)).unwrap());
     }
 
     #[test]
     fn test_byte_vec_codec() {
-        assert_eq!(vec![0xFFu8].encode(), vec![0x02, 0xFFu8]);
-        assert_eq!(Vec::<u8>::new().encode(), vec![0x0]);
-        assert_eq!(vec![0xFFu8], Vec::<u8>::decode(&mut vec![0x02, 0xFFu8].into_iter()).unwrap());
-        assert_eq!(vec![0xFFu8, 0xAF, 0x0],
-                   Vec::<u8>::decode(&mut vec![0xFFu8, 0xAF, 0x0].encode().into_iter()).unwrap());
+        assert_eq!(encode(&vec![0xFFu8]), vec![0x02, 0xFFu8]);
+        assert_eq!(encode(&Vec::<u8>::new()), vec![0x0]);
+        assert_eq!(vec![0xFFu8], decode::<Vec<u8>>(&[0x02, 0xFFu8]).unwrap());
+        let values = vec![0xFFu8, 0xAF, 0x0];
+        assert_eq!(values, decode::<Vec<u8>>(&encode(&values)).unwrap());
     }
 
     #[test]
     fn test_map_codec() {
-        assert_eq!(HashMap::<String, i32>::new().encode(), vec![0x0]);
-        assert_eq!(HashMap::<String, i32>::decode(
-                &mut HashMap::<String, i32>::new().encode().into_iter())
-                .unwrap(),
-                HashMap::<String, i32>::new());
+        assert_eq!(encode(&HashMap::<String, i32>::new()), vec![0x0]);
+        assert_eq!(decode::<HashMap<String, i32>>(&encode(&HashMap::<String, i32>::new())).unwrap(),
+                   HashMap::<String, i32>::new());
         let mut test_map = HashMap::<String, i32>::new();
         test_map.insert(String::from("test"), 1);
-        assert_eq!(test_map, HashMap::<String, i32>::decode(
-                &mut test_map.encode().into_iter()).unwrap());
+        assert_eq!(test_map, decode::<HashMap<String, i32>>(&encode(&test_map)).unwrap());
     }
 }
@@ -0,0 +1,403 @@
+// container.rs
+//
+// (c) 2017 James Crooks
+//
+// Avro Object Container File format: `FileWriter` emits the `Obj\x01`
+// magic, an `avro.schema`/`avro.codec` metadata map, a random 16-byte
+// sync marker, then one or more data blocks framed as
+// `(record-count, byte-length, compressed-body, sync-marker)`.
+// `FileReader` is the matching reader, re-checking the sync marker on
+// every block to catch corruption.
+// (https://avro.apache.org/docs/current/specification/#object-container-files)
+
+use std::collections::HashMap;
+
+use super::codec::AvroCodec;
+use super::io::{Reader, Writer};
+use super::schema::{Decoder, Encoder, SchemaError};
+use super::values::AvroValue;
+
+const MAGIC: [u8; 4] = [0x4F, 0x62, 0x6A, 0x01]; // "Obj" 0x01
+
+/// How many records go in a data block before `FileWriter` starts a new
+/// one, when the caller doesn't pick a size explicitly.
+const DEFAULT_BLOCK_SIZE: usize = 1000;
+
+/// The block compression codec named in a container file's `avro.codec`
+/// metadata entry.
+pub enum Codec {
+    Null,
+    Deflate,
+}
+
+impl Codec {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Codec::Null => "null",
+            Codec::Deflate => "deflate",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Codec> {
+        match name {
+            "null" => Some(Codec::Null),
+            "deflate" => Some(Codec::Deflate),
+            _ => None,
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            Codec::Null => data.to_vec(),
+            Codec::Deflate => deflate_stored(data),
+        }
+    }
+
+    fn decompress(&self, data: &[u8]) -> Option<Vec<u8>> {
+        match *self {
+            Codec::Null => Some(data.to_vec()),
+            Codec::Deflate => inflate_stored(data),
+        }
+    }
+}
+
+/// Encodes `data` as a raw DEFLATE (RFC 1951) stream made entirely of
+/// "stored" (uncompressed) blocks. This produces no compression, but it
+/// is a genuine, standards-compliant deflate stream - stored blocks are
+/// as much a part of RFC 1951 as Huffman-coded ones - so it round-trips
+/// through any conforming deflate implementation, which is what the
+/// `avro.codec = "deflate"` metadata entry promises a reader.
+fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    if data.is_empty() {
+        return vec![0x01, 0x00, 0x00, 0xFF, 0xFF];
+    }
+
+    let mut out = Vec::with_capacity(data.len() + 5 * (data.len() / 0xFFFF + 1));
+    let mut offset = 0;
+    while offset < data.len() {
+        let chunk_len = (data.len() - offset).min(0xFFFF);
+        let is_final = offset + chunk_len == data.len();
+
+        // BFINAL in bit 0, BTYPE = 00 (stored) in bits 1-2, the rest of
+        // the byte is the padding that aligns the block to a byte
+        // boundary before LEN/NLEN.
+        out.push(if is_final { 0x01 } else { 0x00 });
+
+        let len = chunk_len as u16;
+        out.push((len & 0xFF) as u8);
+        out.push((len >> 8) as u8);
+        let nlen = !len;
+        out.push((nlen & 0xFF) as u8);
+        out.push((nlen >> 8) as u8);
+
+        out.extend_from_slice(&data[offset..offset + chunk_len]);
+        offset += chunk_len;
+    }
+    out
+}
+
+/// Decodes a raw DEFLATE stream back to its original bytes. Only
+/// stored blocks (BTYPE 00) are supported - the fixed/dynamic
+/// Huffman block types (01/10) that a general-purpose compressor like
+/// zlib would actually choose are not - so this only round-trips
+/// streams `deflate_stored` (or another stored-blocks-only encoder)
+/// produced.
+fn inflate_stored(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    loop {
+        let header = *data.get(pos)?;
+        pos += 1;
+
+        let is_final = header & 0x1 == 1;
+        let btype = (header >> 1) & 0x3;
+        if btype != 0 {
+            return None;
+        }
+
+        let len_bytes = data.get(pos..pos + 4)?;
+        let len = (len_bytes[0] as u16) | ((len_bytes[1] as u16) << 8);
+        let nlen = (len_bytes[2] as u16) | ((len_bytes[3] as u16) << 8);
+        if nlen != !len {
+            return None;
+        }
+        pos += 4;
+
+        let chunk = data.get(pos..pos + len as usize)?;
+        out.extend_from_slice(chunk);
+        pos += len as usize;
+
+        if is_final {
+            break;
+        }
+    }
+    Some(out)
+}
+
+/// A pseudo-random 16-byte sync marker. There's no `rand` dependency to
+/// reach for, so this draws on `RandomState`'s own OS-seeded per-process
+/// key (the same source `HashMap`'s DoS-resistant hashing relies on)
+/// instead, hashing a different input per 8-byte half so the two halves
+/// don't repeat.
+fn random_sync_marker() -> [u8; 16] {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut marker = [0u8; 16];
+    for chunk in marker.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(chunk.as_ptr() as usize);
+        let bits = hasher.finish();
+        for (i, byte) in chunk.iter_mut().enumerate() {
+            *byte = (bits >> (8 * i)) as u8;
+        }
+    }
+    marker
+}
+
+pub struct FileWriter {
+    schema_json: String,
+    encoder: Encoder,
+    codec: Codec,
+    sync_marker: [u8; 16],
+}
+
+impl FileWriter {
+    pub fn from_avsc(schema_json: &str, codec: Codec) -> Option<FileWriter> {
+        FileWriter::from_avsc_with_sync_marker(schema_json, codec, random_sync_marker())
+    }
+
+    /// As `from_avsc`, but with an explicit sync marker instead of a
+    /// random one - useful for reproducible tests and fixtures.
+    pub fn from_avsc_with_sync_marker(schema_json: &str, codec: Codec, sync_marker: [u8; 16]) -> Option<FileWriter> {
+        let encoder = Encoder::from_avsc(schema_json)?;
+        Some(FileWriter {
+            schema_json: schema_json.to_string(),
+            encoder: encoder,
+            codec: codec,
+            sync_marker: sync_marker,
+        })
+    }
+
+    pub fn sync_marker(&self) -> [u8; 16] {
+        self.sync_marker
+    }
+
+    /// Writes the whole container file - magic, metadata, sync marker,
+    /// and `records` split into `DEFAULT_BLOCK_SIZE`-record blocks.
+    pub fn write(&self, records: &[AvroValue]) -> Result<Vec<u8>, SchemaError> {
+        self.write_in_blocks_of(records, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// As `write`, but with an explicit number of records per data block.
+    pub fn write_in_blocks_of(&self, records: &[AvroValue], block_size: usize) -> Result<Vec<u8>, SchemaError> {
+        let mut writer = Writer::new();
+        writer.write_bytes(&MAGIC);
+
+        let mut metadata = HashMap::new();
+        metadata.insert("avro.schema".to_string(), self.schema_json.clone());
+        metadata.insert("avro.codec".to_string(), self.codec.name().to_string());
+        metadata.encode(&mut writer);
+
+        writer.write_bytes(&self.sync_marker);
+
+        let block_size = block_size.max(1);
+        for chunk in records.chunks(block_size) {
+            let mut body = Vec::new();
+            for record in chunk {
+                body.extend(self.encoder.encode(record)?);
+            }
+            let compressed = self.codec.compress(&body);
+
+            chunk.len().encode(&mut writer);
+            compressed.len().encode(&mut writer);
+            writer.write_bytes(&compressed);
+            writer.write_bytes(&self.sync_marker);
+        }
+
+        Ok(writer.into_bytes())
+    }
+}
+
+pub struct FileReader<'a> {
+    schema_json: String,
+    decoder: Decoder,
+    codec: Codec,
+    sync_marker: [u8; 16],
+    reader: Reader<'a>,
+}
+
+impl<'a> FileReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<FileReader<'a>, SchemaError> {
+        let mut reader = Reader::new(data);
+
+        let magic = reader.take(4).ok_or(SchemaError::Truncated)?;
+        if magic != MAGIC {
+            return Err(SchemaError::BadContainerMagic);
+        }
+
+        let metadata = HashMap::<String, String>::decode(&mut reader).ok_or(SchemaError::Truncated)?;
+        let schema_json = metadata.get("avro.schema").ok_or(SchemaError::MissingSchema)?.clone();
+        let decoder = Decoder::from_avsc(&schema_json).ok_or(SchemaError::MissingSchema)?;
+
+        let codec_name = metadata.get("avro.codec").map(|name| name.as_str()).unwrap_or("null");
+        let codec = Codec::from_name(codec_name).ok_or_else(|| SchemaError::UnknownCodec(codec_name.to_string()))?;
+
+        let sync_bytes = reader.take(16).ok_or(SchemaError::Truncated)?;
+        let mut sync_marker = [0u8; 16];
+        sync_marker.copy_from_slice(sync_bytes);
+
+        Ok(FileReader {
+            schema_json: schema_json,
+            decoder: decoder,
+            codec: codec,
+            sync_marker: sync_marker,
+            reader: reader,
+        })
+    }
+
+    pub fn schema_json(&self) -> &str {
+        &self.schema_json
+    }
+
+    pub fn sync_marker(&self) -> [u8; 16] {
+        self.sync_marker
+    }
+
+    /// Decodes every record across all remaining data blocks, checking
+    /// each block's trailing sync marker against the header's to catch
+    /// a corrupt or truncated file.
+    pub fn read_all(&mut self) -> Result<Vec<AvroValue>, SchemaError> {
+        let mut records = Vec::new();
+
+        while self.reader.remaining() > 0 {
+            let count = usize::decode(&mut self.reader).ok_or(SchemaError::Truncated)?;
+            let byte_len = usize::decode(&mut self.reader).ok_or(SchemaError::Truncated)?;
+            let compressed = self.reader.take(byte_len).ok_or(SchemaError::Truncated)?;
+            let body = self.codec.decompress(compressed).ok_or(SchemaError::DecompressionFailed)?;
+
+            let mut body_reader = Reader::new(&body);
+            for _ in 0..count {
+                records.push(self.decoder.decode(&mut body_reader)?);
+            }
+
+            let marker = self.reader.take(16).ok_or(SchemaError::Truncated)?;
+            if marker != self.sync_marker {
+                return Err(SchemaError::SyncMarkerMismatch);
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Codec, FileReader, FileWriter, MAGIC};
+    use super::super::codec::AvroCodec;
+    use super::super::io::Writer;
+    use super::super::schema::SchemaError;
+    use super::super::values::AvroValue;
+
+    const TEST_SYNC_MARKER: [u8; 16] = [0u8; 16];
+
+    #[test]
+    fn test_round_trip_null_codec() {
+        let writer = FileWriter::from_avsc_with_sync_marker("\"int\"", Codec::Null, TEST_SYNC_MARKER).unwrap();
+        let records = vec![AvroValue::Int(1), AvroValue::Int(2), AvroValue::Int(3)];
+        let bytes = writer.write(&records).unwrap();
+
+        let mut reader = FileReader::new(&bytes).unwrap();
+        assert_eq!(reader.schema_json(), "\"int\"");
+        let decoded = reader.read_all().unwrap();
+        assert_eq!(decoded.len(), 3);
+        for (expected, actual) in records.iter().zip(decoded.iter()) {
+            match (expected, actual) {
+                (&AvroValue::Int(e), &AvroValue::Int(a)) => assert_eq!(e, a),
+                _ => panic!("expected an Int"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_deflate_codec_across_multiple_blocks() {
+        let writer = FileWriter::from_avsc_with_sync_marker("\"int\"", Codec::Deflate, TEST_SYNC_MARKER).unwrap();
+        let records: Vec<AvroValue> = (0..10).map(AvroValue::Int).collect();
+        let bytes = writer.write_in_blocks_of(&records, 3).unwrap();
+
+        let mut reader = FileReader::new(&bytes).unwrap();
+        let decoded = reader.read_all().unwrap();
+        assert_eq!(decoded.len(), 10);
+        match decoded.last() {
+            Some(&AvroValue::Int(last)) => assert_eq!(last, 9),
+            _ => panic!("expected an Int"),
+        }
+    }
+
+    #[test]
+    fn test_bad_magic_rejected() {
+        let mut bytes = FileWriter::from_avsc_with_sync_marker("\"int\"", Codec::Null, TEST_SYNC_MARKER)
+            .unwrap()
+            .write(&[])
+            .unwrap();
+        bytes[0] = 0x00;
+
+        match FileReader::new(&bytes) {
+            Err(SchemaError::BadContainerMagic) => (),
+            other => panic!("expected BadContainerMagic, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_unknown_codec_rejected() {
+        let mut metadata_writer = Writer::new();
+        metadata_writer.write_bytes(&MAGIC);
+        let mut metadata = HashMap::new();
+        metadata.insert("avro.schema".to_string(), "\"int\"".to_string());
+        metadata.insert("avro.codec".to_string(), "snappy".to_string());
+        metadata.encode(&mut metadata_writer);
+        metadata_writer.write_bytes(&TEST_SYNC_MARKER);
+
+        match FileReader::new(&metadata_writer.into_bytes()) {
+            Err(SchemaError::UnknownCodec(ref name)) => assert_eq!(name, "snappy"),
+            other => panic!("expected UnknownCodec, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_sync_marker_mismatch_detected() {
+        let writer = FileWriter::from_avsc_with_sync_marker("\"int\"", Codec::Null, TEST_SYNC_MARKER).unwrap();
+        let mut bytes = writer.write(&[AvroValue::Int(1)]).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+
+        let mut reader = FileReader::new(&bytes).unwrap();
+        match reader.read_all() {
+            Err(SchemaError::SyncMarkerMismatch) => (),
+            other => panic!("expected SyncMarkerMismatch, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_corrupt_block_length_fails_gracefully_instead_of_panicking() {
+        // A crafted block whose `byte_len` zig-zags to i32::MIN used to
+        // panic in `usize::decode`'s `.abs()` call; it must now fail
+        // with a `SchemaError` like any other corrupt input.
+        let mut writer = Writer::new();
+        writer.write_bytes(&MAGIC);
+        let mut metadata = HashMap::new();
+        metadata.insert("avro.schema".to_string(), "\"int\"".to_string());
+        metadata.insert("avro.codec".to_string(), "null".to_string());
+        metadata.encode(&mut writer);
+        writer.write_bytes(&TEST_SYNC_MARKER);
+
+        1usize.encode(&mut writer);
+        writer.write_bytes(&super::super::codec::encode(&i32::min_value()));
+
+        let bytes = writer.into_bytes();
+        let mut reader = FileReader::new(&bytes).unwrap();
+        assert!(reader.read_all().is_err());
+    }
+}
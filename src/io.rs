@@ -0,0 +1,115 @@
+// io.rs
+//
+// (c) 2017 James Crooks
+//
+// Buffer-backed replacement for the old `Iterator<Item = u8>` byte
+// stream. `Reader` is a cursor into a borrowed `&[u8]` so decoders can
+// slice straight into the input instead of collecting it byte-by-byte,
+// and `Writer` is a thin `Vec<u8>` wrapper so encoders append into one
+// shared buffer instead of allocating (and `Vec::append`-ing) a fresh
+// `Vec` per field.
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data: data, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    pub fn next(&mut self) -> Option<u8> {
+        let byte = self.data.get(self.pos).cloned();
+        if byte.is_some() {
+            self.pos += 1;
+        }
+        byte
+    }
+
+    /// Advances past `n` bytes and returns a zero-copy slice into the
+    /// underlying buffer, or `None` (leaving the cursor untouched) if
+    /// fewer than `n` bytes remain.
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.remaining() {
+            return None;
+        }
+
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Some(slice)
+    }
+}
+
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer { buf: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Writer {
+        Writer { buf: Vec::with_capacity(capacity) }
+    }
+
+    #[inline]
+    pub fn write_byte(&mut self, byte: u8) {
+        self.buf.push(byte);
+    }
+
+    #[inline]
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reader, Writer};
+
+    #[test]
+    fn test_reader_next() {
+        let data = [1u8, 2, 3];
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.next(), Some(1));
+        assert_eq!(reader.next(), Some(2));
+        assert_eq!(reader.next(), Some(3));
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn test_reader_take_zero_copy() {
+        let data = [1u8, 2, 3, 4, 5];
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.take(2), Some(&data[0..2]));
+        assert_eq!(reader.remaining(), 3);
+        assert_eq!(reader.take(10), None);
+        assert_eq!(reader.take(3), Some(&data[2..5]));
+    }
+
+    #[test]
+    fn test_writer_roundtrip() {
+        let mut writer = Writer::new();
+        writer.write_byte(0xFF);
+        writer.write_bytes(&[1, 2, 3]);
+        assert_eq!(writer.into_bytes(), vec![0xFF, 1, 2, 3]);
+    }
+}
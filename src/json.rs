@@ -0,0 +1,298 @@
+// json.rs
+//
+// (c) 2017 James Crooks
+//
+// A minimal JSON reader, just enough of the grammar to parse .avsc
+// schema text (objects, arrays, strings, numbers, bools and null).
+// Avro schemas are themselves JSON, but pulling in a general-purpose
+// JSON crate for this one use is overkill, so we hand-roll it here.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        Some(value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&HashMap<String, Json>> {
+        match *self {
+            Json::Object(ref o) => Some(o),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64().map(|n| n as usize)
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        self.as_f64().map(|n| n as i32)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        self.as_object().and_then(|o| o.get(key))
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else {
+            break;
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some(&'{') => parse_object(chars),
+        Some(&'[') => parse_array(chars),
+        Some(&'"') => parse_string(chars).map(Json::String),
+        Some(&'t') => parse_literal(chars, "true", Json::Bool(true)),
+        Some(&'f') => parse_literal(chars, "false", Json::Bool(false)),
+        Some(&'n') => parse_literal(chars, "null", Json::Null),
+        Some(&c) if c == '-' || c.is_ascii_digit() => parse_number(chars),
+        _ => None,
+    }
+}
+
+fn parse_literal(chars: &mut Peekable<Chars>, literal: &str, value: Json) -> Option<Json> {
+    for expected in literal.chars() {
+        if chars.next() != Some(expected) {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+fn parse_object(chars: &mut Peekable<Chars>) -> Option<Json> {
+    chars.next(); // consume '{'
+    let mut map = HashMap::new();
+    skip_whitespace(chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Some(Json::Object(map));
+    }
+
+    loop {
+        let key = parse_string(chars)?;
+        skip_whitespace(chars);
+        if chars.next() != Some(':') {
+            return None;
+        }
+        let value = parse_value(chars)?;
+        map.insert(key, value);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {
+                skip_whitespace(chars);
+                continue;
+            }
+            Some('}') => break,
+            _ => return None,
+        }
+    }
+
+    Some(Json::Object(map))
+}
+
+fn parse_array(chars: &mut Peekable<Chars>) -> Option<Json> {
+    chars.next(); // consume '['
+    let mut items = Vec::new();
+    skip_whitespace(chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+        return Some(Json::Array(items));
+    }
+
+    loop {
+        let value = parse_value(chars)?;
+        items.push(value);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => break,
+            _ => return None,
+        }
+    }
+
+    Some(Json::Array(items))
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    skip_whitespace(chars);
+    if chars.next() != Some('"') {
+        return None;
+    }
+
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                'r' => s.push('\r'),
+                '"' => s.push('"'),
+                '\\' => s.push('\\'),
+                '/' => s.push('/'),
+                'u' => s.push(parse_unicode_escape(chars)?),
+                other => s.push(other),
+            },
+            c => s.push(c),
+        }
+    }
+
+    Some(s)
+}
+
+/// Parses the four hex digits after a `\u` escape and, per the JSON
+/// grammar, a following `\uXXXX` low surrogate if the first one is a
+/// UTF-16 high surrogate - returning `None` for invalid hex, an
+/// unpaired surrogate, or any other codepoint that isn't valid UTF-8.
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Option<char> {
+    let unit = parse_hex4(chars)?;
+    if !(0xD800..=0xDBFF).contains(&unit) {
+        return char::from_u32(unit);
+    }
+
+    if chars.next()? != '\\' || chars.next()? != 'u' {
+        return None;
+    }
+    let low = parse_hex4(chars)?;
+    if !(0xDC00..=0xDFFF).contains(&low) {
+        return None;
+    }
+
+    let codepoint = 0x10000 + (unit - 0xD800) * 0x400 + (low - 0xDC00);
+    char::from_u32(codepoint)
+}
+
+/// Reads exactly four hex digits off `chars` as a `u32`.
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        value = value * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(value)
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Option<Json> {
+    let mut s = String::new();
+    if chars.peek() == Some(&'-') {
+        s.push(chars.next().unwrap());
+    }
+
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-' {
+            s.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+
+    s.parse::<f64>().ok().map(Json::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn test_parse_primitives() {
+        assert_eq!(Json::parse("null"), Some(Json::Null));
+        assert_eq!(Json::parse("true"), Some(Json::Bool(true)));
+        assert_eq!(Json::parse("false"), Some(Json::Bool(false)));
+        assert_eq!(Json::parse("42"), Some(Json::Number(42f64)));
+        assert_eq!(Json::parse("-1.5"), Some(Json::Number(-1.5f64)));
+        assert_eq!(Json::parse("\"hi\""), Some(Json::String(String::from("hi"))));
+    }
+
+    #[test]
+    fn test_parse_array() {
+        assert_eq!(Json::parse("[1, 2, 3]"),
+                   Some(Json::Array(vec![Json::Number(1f64),
+                                         Json::Number(2f64),
+                                         Json::Number(3f64)])));
+        assert_eq!(Json::parse("[]"), Some(Json::Array(vec![])));
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let parsed = Json::parse("{\"type\": \"string\"}").unwrap();
+        assert_eq!(parsed.get("type").and_then(Json::as_str), Some("string"));
+    }
+
+    #[test]
+    fn test_parse_string_with_unicode_escape() {
+        assert_eq!(Json::parse("\"\\u0041\""), Some(Json::String(String::from("A"))));
+        assert_eq!(Json::parse("\"caf\\u00e9\""), Some(Json::String(String::from("caf\u{e9}"))));
+    }
+
+    #[test]
+    fn test_parse_string_with_surrogate_pair_escape() {
+        // U+1F600 (grinning face) as its UTF-16 surrogate pair.
+        assert_eq!(Json::parse("\"\\ud83d\\ude00\""), Some(Json::String(String::from("\u{1F600}"))));
+    }
+
+    #[test]
+    fn test_parse_string_rejects_lone_surrogate_escape() {
+        assert_eq!(Json::parse("\"\\ud83d\""), None);
+    }
+
+    #[test]
+    fn test_parse_nested_schema() {
+        let schema = "{\"type\": \"record\", \"name\": \"Foo\", \"fields\": \
+                       [{\"name\": \"a\", \"type\": \"int\"}]}";
+        let parsed = Json::parse(schema).unwrap();
+        let fields = parsed.get("fields").and_then(Json::as_array).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].get("name").and_then(Json::as_str), Some("a"));
+    }
+}
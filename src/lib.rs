@@ -0,0 +1,12 @@
+// lib.rs
+//
+// avro-rs: a small, dependency-free Avro codec
+// (c) 2017 James Crooks
+
+pub mod codec;
+pub mod container;
+pub mod io;
+pub mod json;
+pub mod ordered;
+pub mod schema;
+pub mod values;
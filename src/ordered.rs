@@ -0,0 +1,248 @@
+// ordered.rs
+//
+// (c) 2017 James Crooks
+//
+// An alternate, order-preserving encoding for `AvroValue`: the raw
+// byte output of `encode_ordered` sorts lexicographically in the same
+// order as the logical values, so it can be used directly as a sort
+// key in a byte-ordered store (an LSM tree, a B-tree keyed on raw
+// bytes, ...). This is unrelated to the little-endian `AvroCodec` wire
+// format and lives entirely behind its own `OrderedCodec` trait so the
+// standard Avro encoding is untouched.
+
+use std::mem;
+
+use super::io::Writer;
+use super::values::AvroValue;
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_BYTES: u8 = 5;
+
+pub trait OrderedCodec {
+    /// Encodes `self` so that `a.encode_ordered() < b.encode_ordered()`
+    /// (compared as raw bytes) iff `a < b` logically, for `a` and `b` of
+    /// the same variant. `Int`, `Long`, `Float`, `Double` and `Decimal`
+    /// share one tag but differ in width and bit layout, so values of
+    /// two different numeric variants don't compare by magnitude.
+    /// Returns `None` for `Map`, which has no well-defined sort order
+    /// over its entries.
+    fn encode_ordered(&self) -> Option<Vec<u8>>;
+}
+
+impl OrderedCodec for AvroValue {
+    fn encode_ordered(&self) -> Option<Vec<u8>> {
+        let mut writer = Writer::new();
+        write_ordered(self, &mut writer)?;
+        Some(writer.into_bytes())
+    }
+}
+
+fn write_ordered(value: &AvroValue, writer: &mut Writer) -> Option<()> {
+    match *value {
+        AvroValue::Null => {
+            writer.write_byte(TAG_NULL);
+            Some(())
+        }
+        AvroValue::Boolean(false) => {
+            writer.write_byte(TAG_FALSE);
+            Some(())
+        }
+        AvroValue::Boolean(true) => {
+            writer.write_byte(TAG_TRUE);
+            Some(())
+        }
+        AvroValue::Int(i) => {
+            writer.write_byte(TAG_NUMBER);
+            write_be_u32(writer, order_i32_bits(i));
+            Some(())
+        }
+        AvroValue::Long(i) => {
+            writer.write_byte(TAG_NUMBER);
+            write_be_u64(writer, order_i64_bits(i));
+            Some(())
+        }
+        AvroValue::Float(f) => {
+            writer.write_byte(TAG_NUMBER);
+            write_be_u32(writer, order_f32_bits(f));
+            Some(())
+        }
+        AvroValue::Double(f) => {
+            writer.write_byte(TAG_NUMBER);
+            write_be_u64(writer, order_f64_bits(f));
+            Some(())
+        }
+        AvroValue::String(ref s) => {
+            writer.write_byte(TAG_STRING);
+            write_stuffed(writer, s.as_bytes());
+            Some(())
+        }
+        AvroValue::Bytes(ref b) => {
+            writer.write_byte(TAG_BYTES);
+            write_stuffed(writer, b);
+            Some(())
+        }
+        AvroValue::Fixed(ref f) => {
+            writer.write_byte(TAG_BYTES);
+            write_stuffed(writer, f.data());
+            Some(())
+        }
+        // Symbols sort by name, same as any other string key.
+        AvroValue::Enum(ref e) => {
+            writer.write_byte(TAG_STRING);
+            write_stuffed(writer, e.symbol().as_bytes());
+            Some(())
+        }
+        // The branch index isn't part of the logical value, so unions
+        // encode transparently as whichever branch they resolved to.
+        AvroValue::Union(ref u) => write_ordered(u.value(), writer),
+        // A tuple-style composite key: each element's ordered bytes,
+        // back to back, sorts the same way the elements do in order.
+        AvroValue::Array(ref a) => {
+            for item in a.items() {
+                write_ordered(item, writer)?;
+            }
+            Some(())
+        }
+        AvroValue::Record(ref r) => {
+            for &(_, ref v) in &r.fields {
+                write_ordered(v, writer)?;
+            }
+            Some(())
+        }
+        AvroValue::Map(_) => None,
+        // Sorts correctly against other decimals of the same scale,
+        // same as comparing the underlying unscaled integers directly.
+        AvroValue::Decimal(ref d) => {
+            writer.write_byte(TAG_NUMBER);
+            write_be_u64(writer, order_i64_bits(d.unscaled()));
+            Some(())
+        }
+    }
+}
+
+fn order_i32_bits(x: i32) -> u32 {
+    (x as u32) ^ (1u32 << 31)
+}
+
+fn order_i64_bits(x: i64) -> u64 {
+    (x as u64) ^ (1u64 << 63)
+}
+
+fn order_f32_bits(f: f32) -> u32 {
+    let bits: u32 = unsafe { mem::transmute(f) };
+    if bits & (1u32 << 31) == 0 {
+        bits ^ (1u32 << 31)
+    } else {
+        !bits
+    }
+}
+
+fn order_f64_bits(f: f64) -> u64 {
+    let bits: u64 = unsafe { mem::transmute(f) };
+    if bits & (1u64 << 63) == 0 {
+        bits ^ (1u64 << 63)
+    } else {
+        !bits
+    }
+}
+
+fn write_be_u32(writer: &mut Writer, v: u32) {
+    for i in (0..4).rev() {
+        writer.write_byte((v >> (8 * i)) as u8);
+    }
+}
+
+fn write_be_u64(writer: &mut Writer, v: u64) {
+    for i in (0..8).rev() {
+        writer.write_byte((v >> (8 * i)) as u8);
+    }
+}
+
+/// Writes `bytes` with `0x00` escaped to `0x00 0xFF` and terminated by
+/// `0x00 0x00`, rather than a varint length prefix, since a length
+/// prefix would break prefix ordering (`"ab"` would sort after `"b"`).
+fn write_stuffed(writer: &mut Writer, bytes: &[u8]) {
+    for &byte in bytes {
+        if byte == 0x00 {
+            writer.write_byte(0x00);
+            writer.write_byte(0xFF);
+        } else {
+            writer.write_byte(byte);
+        }
+    }
+    writer.write_byte(0x00);
+    writer.write_byte(0x00);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderedCodec;
+    use super::super::values::AvroValue;
+
+    #[test]
+    fn test_null_false_true_sort_order() {
+        let null = AvroValue::Null.encode_ordered().unwrap();
+        let f = AvroValue::Boolean(false).encode_ordered().unwrap();
+        let t = AvroValue::Boolean(true).encode_ordered().unwrap();
+        assert!(null < f);
+        assert!(f < t);
+    }
+
+    #[test]
+    fn test_signed_int_sort_order() {
+        let values = [i32::min_value(), -1, 0, 1, i32::max_value()];
+        let mut encoded: Vec<Vec<u8>> = values.iter()
+            .map(|v| AvroValue::Int(*v).encode_ordered().unwrap())
+            .collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+
+        // And the tag puts every int/long/float/double in one bucket.
+        for e in &mut encoded {
+            assert_eq!(e[0], super::TAG_NUMBER);
+        }
+    }
+
+    #[test]
+    fn test_float_sort_order() {
+        let values = [f64::MIN, -1.5f64, -0.0f64, 0.0f64, 1.5f64, f64::MAX];
+        let encoded: Vec<Vec<u8>> = values.iter()
+            .map(|v| AvroValue::Double(*v).encode_ordered().unwrap())
+            .collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+    }
+
+    #[test]
+    fn test_string_sort_order_matches_lexicographic() {
+        let words = ["", "a", "ab", "b"];
+        let encoded: Vec<Vec<u8>> = words.iter()
+            .map(|w| AvroValue::String(String::from(*w)).encode_ordered().unwrap())
+            .collect();
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+    }
+
+    #[test]
+    fn test_string_with_nul_byte_stuffing_preserves_order() {
+        let a = AvroValue::String(String::from("a")).encode_ordered().unwrap();
+        let a_nul = AvroValue::String(String::from("a\u{0}")).encode_ordered().unwrap();
+        let b = AvroValue::String(String::from("b")).encode_ordered().unwrap();
+        assert!(a < a_nul);
+        assert!(a_nul < b);
+    }
+
+    #[test]
+    fn test_map_has_no_ordering() {
+        use std::collections::HashMap;
+        use super::super::values::AvroMap;
+        assert_eq!(AvroValue::Map(AvroMap::new(HashMap::new())).encode_ordered(), None);
+    }
+}
@@ -4,58 +4,146 @@
 //
 // Schema object for en-/de-coding ad-hoc Avro objects
 
-use super::codec::{AvroCodec};
-use super::values::AvroValue;
+use super::codec::AvroCodec;
+use super::io::{Reader, Writer};
+use super::json::Json;
+use super::values::{AvroArray, AvroDecimal, AvroEnum, AvroFixed, AvroMap, AvroRecord, AvroUnion, AvroValue};
+
+#[derive(Debug)]
+pub enum SchemaError {
+    /// The `AvroValue` handed to the encoder doesn't match the shape
+    /// the schema expects at this position.
+    TypeMismatch,
+    /// A record value was missing a field the schema requires and the
+    /// field has no default.
+    MissingField(String),
+    /// An enum value's symbol isn't one the schema declares.
+    UnknownSymbol,
+    /// A union value didn't match any of the schema's branch types.
+    NoMatchingBranch,
+    /// Ran out of bytes mid-decode.
+    Truncated,
+    /// A single-object-encoded buffer didn't start with the `0xC3 0x01`
+    /// marker bytes.
+    BadSingleObjectMarker,
+    /// A single-object-encoded buffer's schema fingerprint doesn't
+    /// match this decoder's schema.
+    FingerprintMismatch,
+    /// An Object Container File didn't start with the `Obj\x01` magic.
+    BadContainerMagic,
+    /// An Object Container File's metadata had no usable `avro.schema`
+    /// entry (missing, or not a schema this crate can parse).
+    MissingSchema,
+    /// An Object Container File's `avro.codec` metadata named a codec
+    /// this crate doesn't implement.
+    UnknownCodec(String),
+    /// A data block's trailing sync marker didn't match the file
+    /// header's, meaning the file is corrupt or truncated.
+    SyncMarkerMismatch,
+    /// A compressed data block's bytes didn't decompress under its codec.
+    DecompressionFailed,
+}
 
 pub struct Schema {
-    avro_schema: AvroSchema
+    avro_schema: AvroType,
 }
 
 impl Schema {
     pub fn from_avsc(schema: &str) -> Option<Schema> {
-        None
+        let json = Json::parse(schema)?;
+        let avro_schema = AvroType::from_json(&json)?;
+        Some(Schema { avro_schema: avro_schema })
+    }
+
+    /// The CRC-64-AVRO Rabin fingerprint of this schema's parsing
+    /// canonical form, as used to identify the schema in the single-object
+    /// encoding (https://avro.apache.org/docs/current/specification/#single-object-encoding).
+    pub fn fingerprint(&self) -> u64 {
+        rabin_fingerprint(canonical_form(&self.avro_schema).as_bytes())
     }
 }
 
+/// The two marker bytes that open every Avro single-object encoding.
+const SINGLE_OBJECT_MARKER: [u8; 2] = [0xC3, 0x01];
+
 pub struct Encoder {
-    schema: Schema
+    schema: Schema,
 }
 
 impl Encoder {
     pub fn new(schema: Schema) -> Encoder {
-        Encoder {
-            schema: schema,
-        }
+        Encoder { schema: schema }
     }
 
     pub fn from_avsc(schema: &str) -> Option<Encoder> {
         Schema::from_avsc(schema).map(Encoder::new)
     }
+
+    pub fn encode(&self, value: &AvroValue) -> Result<Vec<u8>, SchemaError> {
+        let mut writer = Writer::new();
+        encode_value(&self.schema.avro_schema, value, &mut writer)?;
+        Ok(writer.into_bytes())
+    }
+
+    /// Encodes `value` as an Avro single object: the `0xC3 0x01` marker,
+    /// the schema's little-endian Rabin fingerprint, then the normal
+    /// Avro body, so other Avro tooling can identify the schema used.
+    pub fn encode_single_object(&self, value: &AvroValue) -> Result<Vec<u8>, SchemaError> {
+        let mut writer = Writer::new();
+        writer.write_bytes(&SINGLE_OBJECT_MARKER);
+
+        let mut fingerprint_bytes = [0u8; 8];
+        let fingerprint = self.schema.fingerprint();
+        for i in 0..8 {
+            fingerprint_bytes[i] = (fingerprint >> (8 * i)) as u8;
+        }
+        writer.write_bytes(&fingerprint_bytes);
+
+        encode_value(&self.schema.avro_schema, value, &mut writer)?;
+        Ok(writer.into_bytes())
+    }
 }
 
 pub struct Decoder {
-    schema: Schema
+    schema: Schema,
 }
 
 impl Decoder {
     pub fn new(schema: Schema) -> Decoder {
-        Decoder {
-            schema: schema,
-        }
+        Decoder { schema: schema }
     }
 
     pub fn from_avsc(schema: &str) -> Option<Decoder> {
         Schema::from_avsc(schema).map(Decoder::new)
     }
-}
 
-enum AvroSchema {
-    Record(RecordSchema),
-    Enum(EnumSchema),
-    Fixed(FixedSchema),
+    pub fn decode(&self, reader: &mut Reader) -> Result<AvroValue, SchemaError> {
+        decode_value(&self.schema.avro_schema, reader)
+    }
+
+    /// Decodes an Avro single object, verifying the `0xC3 0x01` marker
+    /// and the schema fingerprint before decoding the body.
+    pub fn decode_single_object(&self, reader: &mut Reader) -> Result<AvroValue, SchemaError> {
+        let marker = reader.take(2).ok_or(SchemaError::Truncated)?;
+        if marker != SINGLE_OBJECT_MARKER {
+            return Err(SchemaError::BadSingleObjectMarker);
+        }
+
+        let fingerprint_bytes = reader.take(8).ok_or(SchemaError::Truncated)?;
+        let mut fingerprint = 0u64;
+        for (i, &byte) in fingerprint_bytes.iter().enumerate() {
+            fingerprint |= (byte as u64) << (8 * i);
+        }
+
+        if fingerprint != self.schema.fingerprint() {
+            return Err(SchemaError::FingerprintMismatch);
+        }
+
+        decode_value(&self.schema.avro_schema, reader)
+    }
 }
 
-enum AvroType {
+pub(crate) enum AvroType {
     Null,
     Boolean,
     Int,
@@ -70,35 +158,932 @@ enum AvroType {
     Array(ArraySchema),
     Map(MapSchema),
     Union(UnionSchema),
+    Logical(LogicalType, Box<AvroType>),
+}
+
+/// An Avro logical type: a semantic annotation layered on top of a
+/// base primitive, per https://avro.apache.org/docs/current/specification/#logical-types.
+/// The wire encoding for `Date`/`TimestampMillis`/`TimestampMicros`/`Uuid`
+/// is identical to their base type's, so only `Decimal` needs its own
+/// `AvroValue` variant and encode/decode logic.
+pub(crate) enum LogicalType {
+    Decimal { precision: usize, scale: usize },
+    Date,
+    TimestampMillis,
+    TimestampMicros,
+    Uuid,
+}
+
+impl LogicalType {
+    /// Reads the `logicalType` (and, for `decimal`, `precision`/`scale`)
+    /// attributes off a type's JSON object. Returns `None` - so the
+    /// caller falls back to the bare primitive - for a name this crate
+    /// doesn't recognize or one that doesn't apply to `base`, per the
+    /// spec's "unknown logical types ... should be ignored" rule.
+    fn from_json(json: &Json, base: &AvroType) -> Option<LogicalType> {
+        let name = json.get("logicalType")?.as_str()?;
+        match (name, base) {
+            ("decimal", &AvroType::Bytes) | ("decimal", &AvroType::Fixed(_)) => {
+                let precision = json.get("precision")?.as_usize()?;
+                let scale = json.get("scale").and_then(Json::as_usize).unwrap_or(0);
+                Some(LogicalType::Decimal { precision: precision, scale: scale })
+            }
+            ("date", &AvroType::Int) => Some(LogicalType::Date),
+            ("timestamp-millis", &AvroType::Long) => Some(LogicalType::TimestampMillis),
+            ("timestamp-micros", &AvroType::Long) => Some(LogicalType::TimestampMicros),
+            ("uuid", &AvroType::String) => Some(LogicalType::Uuid),
+            _ => None,
+        }
+    }
 }
 
-struct Field {
-    name: String,
-    value: AvroType,
-    default: Option<Box<AvroValue>>,
+pub(crate) struct Field {
+    pub(crate) name: String,
+    pub(crate) value: AvroType,
+    pub(crate) default: Option<Box<AvroValue>>,
 }
 
-struct RecordSchema {
-    fields: Vec<Field>,
+pub(crate) struct RecordSchema {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<Field>,
 }
 
-struct EnumSchema {
-    symbols: Vec<String>,
+pub(crate) struct EnumSchema {
+    pub(crate) name: String,
+    pub(crate) symbols: Vec<String>,
 }
 
-struct FixedSchema {
-    size: usize,
+pub(crate) struct FixedSchema {
+    pub(crate) name: String,
+    pub(crate) size: usize,
 }
 
-struct ArraySchema {
-    typ: Box<AvroType>
+pub(crate) struct ArraySchema {
+    pub(crate) typ: Box<AvroType>,
 }
 
-struct MapSchema {
-    vtype: Box<AvroType>
+pub(crate) struct MapSchema {
+    pub(crate) vtype: Box<AvroType>,
 }
 
-struct UnionSchema {
-    types: Vec<AvroType>
+pub(crate) struct UnionSchema {
+    pub(crate) types: Vec<AvroType>,
+}
+
+impl AvroType {
+    fn from_json(json: &Json) -> Option<AvroType> {
+        match *json {
+            Json::String(ref name) => AvroType::from_name(name),
+            Json::Array(ref branches) => {
+                let mut types = Vec::with_capacity(branches.len());
+                for branch in branches {
+                    types.push(AvroType::from_json(branch)?);
+                }
+                Some(AvroType::Union(UnionSchema { types: types }))
+            }
+            Json::Object(_) => AvroType::from_object(json),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<AvroType> {
+        match name {
+            "null" => Some(AvroType::Null),
+            "boolean" => Some(AvroType::Boolean),
+            "int" => Some(AvroType::Int),
+            "long" => Some(AvroType::Long),
+            "float" => Some(AvroType::Float),
+            "double" => Some(AvroType::Double),
+            "bytes" => Some(AvroType::Bytes),
+            "string" => Some(AvroType::String),
+            _ => None,
+        }
+    }
+
+    fn from_object(json: &Json) -> Option<AvroType> {
+        let type_field = json.get("type")?;
+        let type_name = match *type_field {
+            Json::String(ref name) => name.as_str(),
+            // `{"type": {"type": "array", ...}}`-style nesting.
+            _ => return AvroType::from_json(type_field),
+        };
+
+        let base = match type_name {
+            "record" => RecordSchema::from_json(json).map(AvroType::Record)?,
+            "enum" => EnumSchema::from_json(json).map(AvroType::Enum)?,
+            "fixed" => FixedSchema::from_json(json).map(AvroType::Fixed)?,
+            "array" => {
+                let items = AvroType::from_json(json.get("items")?)?;
+                AvroType::Array(ArraySchema { typ: Box::new(items) })
+            }
+            "map" => {
+                let values = AvroType::from_json(json.get("values")?)?;
+                AvroType::Map(MapSchema { vtype: Box::new(values) })
+            }
+            other => AvroType::from_name(other)?,
+        };
+
+        match LogicalType::from_json(json, &base) {
+            Some(logical) => Some(AvroType::Logical(logical, Box::new(base))),
+            None => Some(base),
+        }
+    }
 }
 
+impl RecordSchema {
+    fn from_json(json: &Json) -> Option<RecordSchema> {
+        let name = json.get("name")?.as_str()?.to_string();
+        let fields_json = json.get("fields")?.as_array()?;
+        let mut fields = Vec::with_capacity(fields_json.len());
+        for field_json in fields_json {
+            fields.push(Field::from_json(field_json)?);
+        }
+        Some(RecordSchema { name: name, fields: fields })
+    }
+}
+
+impl Field {
+    fn from_json(json: &Json) -> Option<Field> {
+        let name = json.get("name")?.as_str()?.to_string();
+        let value = AvroType::from_json(json.get("type")?)?;
+        let default = match json.get("default") {
+            Some(default_json) => Some(Box::new(resolve_default(default_json, &value)?)),
+            None => None,
+        };
+
+        Some(Field { name: name, value: value, default: default })
+    }
+}
+
+impl EnumSchema {
+    fn from_json(json: &Json) -> Option<EnumSchema> {
+        let name = json.get("name")?.as_str()?.to_string();
+        let symbols_json = json.get("symbols")?.as_array()?;
+        let mut symbols = Vec::with_capacity(symbols_json.len());
+        for symbol in symbols_json {
+            symbols.push(symbol.as_str()?.to_string());
+        }
+        Some(EnumSchema { name: name, symbols: symbols })
+    }
+}
+
+impl FixedSchema {
+    fn from_json(json: &Json) -> Option<FixedSchema> {
+        let name = json.get("name")?.as_str()?.to_string();
+        let size = json.get("size")?.as_usize()?;
+        Some(FixedSchema { name: name, size: size })
+    }
+}
+
+/// Resolves a JSON `default` attribute into the `AvroValue` it denotes,
+/// per the avsc spec's JSON encoding of default values.
+fn resolve_default(json: &Json, typ: &AvroType) -> Option<AvroValue> {
+    match *typ {
+        AvroType::Null => Some(AvroValue::Null),
+        AvroType::Boolean => json.as_bool().map(AvroValue::Boolean),
+        AvroType::Int => json.as_i32().map(AvroValue::Int),
+        AvroType::Long => json.as_f64().map(|n| AvroValue::Long(n as i64)),
+        AvroType::Float => json.as_f64().map(|n| AvroValue::Float(n as f32)),
+        AvroType::Double => json.as_f64().map(AvroValue::Double),
+        AvroType::Bytes => json.as_str().map(|s| AvroValue::Bytes(s.bytes().collect())),
+        AvroType::String => json.as_str().map(|s| AvroValue::String(s.to_string())),
+        AvroType::Fixed(ref f) => {
+            json.as_str().and_then(|s| AvroFixed::with_data(f.size, s.bytes().collect()))
+                .map(AvroValue::Fixed)
+        }
+        AvroType::Enum(ref e) => {
+            json.as_str().and_then(|s| {
+                if e.symbols.iter().any(|symbol| symbol == s) {
+                    Some(AvroValue::Enum(AvroEnum { symbol: s.to_string() }))
+                } else {
+                    None
+                }
+            })
+        }
+        AvroType::Array(ref a) => {
+            let items = json.as_array()?;
+            let mut values = Vec::with_capacity(items.len());
+            for item in items {
+                values.push(resolve_default(item, &a.typ)?);
+            }
+            Some(AvroValue::Array(AvroArray { items: values }))
+        }
+        AvroType::Map(ref m) => {
+            let object = json.as_object()?;
+            let mut entries = ::std::collections::HashMap::with_capacity(object.len());
+            for (key, value_json) in object {
+                entries.insert(key.clone(), resolve_default(value_json, &m.vtype)?);
+            }
+            Some(AvroValue::Map(AvroMap { entries: entries }))
+        }
+        AvroType::Record(ref r) => {
+            let object = json.as_object()?;
+            let mut fields = Vec::with_capacity(r.fields.len());
+            for field in &r.fields {
+                let field_json = object.get(&field.name)?;
+                fields.push((field.name.clone(), resolve_default(field_json, &field.value)?));
+            }
+            Some(AvroValue::Record(AvroRecord { fields: fields }))
+        }
+        // A union's default is always encoded as a JSON value of its
+        // first branch's type.
+        AvroType::Union(ref u) => u.types.first().and_then(|first| resolve_default(json, first)),
+        AvroType::Logical(ref logical, ref base) => match *logical {
+            LogicalType::Decimal { precision, scale } => json.as_str().map(|s| {
+                let bytes: Vec<u8> = s.bytes().collect();
+                AvroValue::Decimal(AvroDecimal::new(decimal_from_bytes(&bytes), precision, scale))
+            }),
+            _ => resolve_default(json, base),
+        },
+    }
+}
+
+fn matches_type(typ: &AvroType, value: &AvroValue) -> bool {
+    match (typ, value) {
+        (&AvroType::Null, &AvroValue::Null) => true,
+        (&AvroType::Boolean, &AvroValue::Boolean(_)) => true,
+        (&AvroType::Int, &AvroValue::Int(_)) => true,
+        (&AvroType::Long, &AvroValue::Long(_)) => true,
+        (&AvroType::Float, &AvroValue::Float(_)) => true,
+        (&AvroType::Double, &AvroValue::Double(_)) => true,
+        (&AvroType::Bytes, &AvroValue::Bytes(_)) => true,
+        (&AvroType::String, &AvroValue::String(_)) => true,
+        (&AvroType::Record(_), &AvroValue::Record(_)) => true,
+        (&AvroType::Enum(_), &AvroValue::Enum(_)) => true,
+        (&AvroType::Fixed(_), &AvroValue::Fixed(_)) => true,
+        (&AvroType::Array(_), &AvroValue::Array(_)) => true,
+        (&AvroType::Map(_), &AvroValue::Map(_)) => true,
+        (&AvroType::Logical(ref logical, ref base), _) => match *logical {
+            LogicalType::Decimal { .. } => match *value {
+                AvroValue::Decimal(_) => true,
+                _ => false,
+            },
+            _ => matches_type(base, value),
+        },
+        _ => false,
+    }
+}
+
+fn encode_value(typ: &AvroType, value: &AvroValue, writer: &mut Writer) -> Result<(), SchemaError> {
+    match *typ {
+        AvroType::Null => match *value {
+            AvroValue::Null => Ok(()),
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Boolean => match *value {
+            AvroValue::Boolean(ref b) => {
+                b.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Int => match *value {
+            AvroValue::Int(ref i) => {
+                i.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Long => match *value {
+            AvroValue::Long(ref i) => {
+                i.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Float => match *value {
+            AvroValue::Float(ref f) => {
+                f.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Double => match *value {
+            AvroValue::Double(ref f) => {
+                f.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Bytes => match *value {
+            AvroValue::Bytes(ref b) => {
+                b.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::String => match *value {
+            AvroValue::String(ref s) => {
+                s.encode(writer);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Fixed(ref schema) => match *value {
+            AvroValue::Fixed(ref f) if f.data().len() == schema.size => {
+                writer.write_bytes(f.data());
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Enum(ref schema) => match *value {
+            AvroValue::Enum(ref e) => {
+                match schema.symbols.iter().position(|symbol| *symbol == e.symbol) {
+                    Some(index) => {
+                        (index as i32).encode(writer);
+                        Ok(())
+                    }
+                    None => Err(SchemaError::UnknownSymbol),
+                }
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Record(ref schema) => match *value {
+            AvroValue::Record(ref record) => {
+                for field in &schema.fields {
+                    let field_value = record.fields.iter()
+                        .find(|entry| entry.0 == field.name)
+                        .map(|entry| &entry.1);
+
+                    match field_value {
+                        Some(v) => encode_value(&field.value, v, writer)?,
+                        None => match field.default {
+                            Some(ref default) => encode_value(&field.value, default, writer)?,
+                            None => return Err(SchemaError::MissingField(field.name.clone())),
+                        },
+                    };
+                }
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Array(ref schema) => match *value {
+            AvroValue::Array(ref array) => {
+                array.items.len().encode(writer);
+                for item in &array.items {
+                    encode_value(&schema.typ, item, writer)?;
+                }
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Map(ref schema) => match *value {
+            AvroValue::Map(ref map) => {
+                if map.entries.is_empty() {
+                    writer.write_byte(0x0);
+                    return Ok(());
+                }
+
+                map.entries.len().encode(writer);
+                for (key, item) in &map.entries {
+                    key.encode(writer);
+                    encode_value(&schema.vtype, item, writer)?;
+                }
+                writer.write_byte(0x0);
+                Ok(())
+            }
+            _ => Err(SchemaError::TypeMismatch),
+        },
+        AvroType::Union(ref schema) => {
+            let (index, branch_type, branch_value) = match *value {
+                AvroValue::Union(ref u) => {
+                    let branch = schema.types.get(u.index as usize)
+                        .ok_or(SchemaError::NoMatchingBranch)?;
+                    (u.index, branch, &*u.value)
+                }
+                ref bare => {
+                    let position = schema.types.iter()
+                        .position(|branch| matches_type(branch, bare))
+                        .ok_or(SchemaError::NoMatchingBranch)?;
+                    (position as i32, &schema.types[position], bare)
+                }
+            };
+
+            index.encode(writer);
+            encode_value(branch_type, branch_value, writer)
+        }
+        AvroType::Logical(ref logical, ref base) => match *logical {
+            LogicalType::Decimal { .. } => match *value {
+                AvroValue::Decimal(ref d) => {
+                    let bytes = decimal_bytes(d.unscaled, base)?;
+                    match **base {
+                        AvroType::Bytes => {
+                            bytes.encode(writer);
+                            Ok(())
+                        }
+                        AvroType::Fixed(_) => {
+                            writer.write_bytes(&bytes);
+                            Ok(())
+                        }
+                        _ => Err(SchemaError::TypeMismatch),
+                    }
+                }
+                _ => Err(SchemaError::TypeMismatch),
+            },
+            _ => encode_value(base, value, writer),
+        },
+    }
+}
+
+fn decode_value(typ: &AvroType, reader: &mut Reader) -> Result<AvroValue, SchemaError> {
+    match *typ {
+        AvroType::Null => Ok(AvroValue::Null),
+        AvroType::Boolean => bool::decode(reader).map(AvroValue::Boolean).ok_or(SchemaError::Truncated),
+        AvroType::Int => i32::decode(reader).map(AvroValue::Int).ok_or(SchemaError::Truncated),
+        AvroType::Long => i64::decode(reader).map(AvroValue::Long).ok_or(SchemaError::Truncated),
+        AvroType::Float => f32::decode(reader).map(AvroValue::Float).ok_or(SchemaError::Truncated),
+        AvroType::Double => f64::decode(reader).map(AvroValue::Double).ok_or(SchemaError::Truncated),
+        AvroType::Bytes => Vec::<u8>::decode(reader).map(AvroValue::Bytes).ok_or(SchemaError::Truncated),
+        AvroType::String => String::decode(reader).map(AvroValue::String).ok_or(SchemaError::Truncated),
+        AvroType::Fixed(ref schema) => {
+            let data = reader.take(schema.size).ok_or(SchemaError::Truncated)?;
+            AvroFixed::with_data(schema.size, data.to_vec()).map(AvroValue::Fixed).ok_or(SchemaError::Truncated)
+        }
+        AvroType::Enum(ref schema) => {
+            let index = i32::decode(reader).ok_or(SchemaError::Truncated)?;
+            schema.symbols.get(index as usize)
+                .map(|symbol| AvroValue::Enum(AvroEnum { symbol: symbol.clone() }))
+                .ok_or(SchemaError::UnknownSymbol)
+        }
+        AvroType::Record(ref schema) => {
+            let mut fields = Vec::with_capacity(schema.fields.len());
+            for field in &schema.fields {
+                let decoded = decode_value(&field.value, reader)?;
+                fields.push((field.name.clone(), decoded));
+            }
+            Ok(AvroValue::Record(AvroRecord { fields: fields }))
+        }
+        AvroType::Array(ref schema) => {
+            let len = usize::decode(reader).ok_or(SchemaError::Truncated)?;
+            let mut items = Vec::with_capacity(len);
+            for _ in 0..len {
+                items.push(decode_value(&schema.typ, reader)?);
+            }
+            Ok(AvroValue::Array(AvroArray { items: items }))
+        }
+        AvroType::Map(ref schema) => {
+            // Maps are Avro "blocked" data: a count-prefixed run of
+            // entries, repeated until a block of count zero terminates
+            // the sequence (which `encode_value` always writes, even
+            // after a single block).
+            let mut entries = ::std::collections::HashMap::new();
+            loop {
+                let len = usize::decode(reader).ok_or(SchemaError::Truncated)?;
+                if len == 0 {
+                    break;
+                }
+
+                for _ in 0..len {
+                    let key = String::decode(reader).ok_or(SchemaError::Truncated)?;
+                    let value = decode_value(&schema.vtype, reader)?;
+                    entries.insert(key, value);
+                }
+            }
+            Ok(AvroValue::Map(AvroMap { entries: entries }))
+        }
+        AvroType::Union(ref schema) => {
+            let index = i32::decode(reader).ok_or(SchemaError::Truncated)?;
+            let branch_type = schema.types.get(index as usize).ok_or(SchemaError::NoMatchingBranch)?;
+            let value = decode_value(branch_type, reader)?;
+            Ok(AvroValue::Union(AvroUnion { index: index, value: Box::new(value) }))
+        }
+        AvroType::Logical(ref logical, ref base) => match *logical {
+            LogicalType::Decimal { precision, scale } => {
+                let bytes = match **base {
+                    AvroType::Bytes => Vec::<u8>::decode(reader).ok_or(SchemaError::Truncated)?,
+                    AvroType::Fixed(ref schema) => reader.take(schema.size).ok_or(SchemaError::Truncated)?.to_vec(),
+                    _ => return Err(SchemaError::TypeMismatch),
+                };
+                Ok(AvroValue::Decimal(AvroDecimal::new(decimal_from_bytes(&bytes), precision, scale)))
+            }
+            _ => decode_value(base, reader),
+        },
+    }
+}
+
+/// Packs `unscaled` into the two's-complement big-endian byte layout a
+/// `decimal` logical type's base expects: the minimal number of bytes
+/// for a `bytes` base, or exactly `size` sign-extended bytes for a
+/// `fixed` base (erroring if `unscaled` doesn't fit in `size` bytes).
+fn decimal_bytes(unscaled: i64, base: &AvroType) -> Result<Vec<u8>, SchemaError> {
+    match *base {
+        AvroType::Bytes => Ok(twos_complement_minimal(unscaled)),
+        AvroType::Fixed(ref schema) => twos_complement_sized(unscaled, schema.size).ok_or(SchemaError::TypeMismatch),
+        _ => Err(SchemaError::TypeMismatch),
+    }
+}
+
+fn i64_to_be_bytes(value: i64) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    for i in 0..8 {
+        bytes[7 - i] = (value >> (8 * i)) as u8;
+    }
+    bytes
+}
+
+/// The shortest big-endian two's-complement byte string that round-trips
+/// to `value`, i.e. with no redundant leading `0x00`/`0xFF` sign-extension
+/// byte - the same representation `BigInteger.toByteArray()` produces,
+/// which is what other Avro implementations expect for a `bytes` decimal.
+fn twos_complement_minimal(value: i64) -> Vec<u8> {
+    let full = i64_to_be_bytes(value);
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (full[start] == 0x00 && full[start + 1] & 0x80 == 0) ||
+                         (full[start] == 0xFF && full[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    full[start..].to_vec()
+}
+
+/// The big-endian two's-complement encoding of `value` in exactly `size`
+/// bytes (sign-extended), or `None` if `value` doesn't fit in `size` bytes.
+fn twos_complement_sized(value: i64, size: usize) -> Option<Vec<u8>> {
+    let full = i64_to_be_bytes(value);
+    let sign_byte = if value < 0 { 0xFFu8 } else { 0x00u8 };
+
+    if size >= 8 {
+        let mut bytes = vec![sign_byte; size - 8];
+        bytes.extend_from_slice(&full);
+        Some(bytes)
+    } else {
+        let drop = 8 - size;
+        let fits = full[..drop].iter().all(|&b| b == sign_byte) && full[drop] & 0x80 == sign_byte & 0x80;
+        if fits {
+            Some(full[drop..].to_vec())
+        } else {
+            None
+        }
+    }
+}
+
+/// The inverse of `twos_complement_minimal`/`twos_complement_sized`:
+/// reads a big-endian two's-complement byte string back into an `i64`.
+fn decimal_from_bytes(bytes: &[u8]) -> i64 {
+    let mut value: i64 = if bytes.first().map_or(false, |&b| b & 0x80 != 0) { -1 } else { 0 };
+    for &byte in bytes {
+        value = (value << 8) | (byte as i64);
+    }
+    value
+}
+
+/// Renders a schema to Avro's Parsing Canonical Form: primitive names
+/// without the `{"type": ...}` wrapper, only the attributes the spec
+/// keeps (name/type/fields/symbols/items/values/size, in that order),
+/// and no insignificant whitespace. This is what gets fingerprinted,
+/// not the original .avsc text, so two schemas that differ only in
+/// doc strings, field order of JSON keys, or whitespace still agree.
+fn canonical_form(typ: &AvroType) -> String {
+    match *typ {
+        AvroType::Null => "\"null\"".to_string(),
+        AvroType::Boolean => "\"boolean\"".to_string(),
+        AvroType::Int => "\"int\"".to_string(),
+        AvroType::Long => "\"long\"".to_string(),
+        AvroType::Float => "\"float\"".to_string(),
+        AvroType::Double => "\"double\"".to_string(),
+        AvroType::Bytes => "\"bytes\"".to_string(),
+        AvroType::String => "\"string\"".to_string(),
+        AvroType::Record(ref schema) => {
+            let fields: Vec<String> = schema.fields.iter()
+                .map(|field| format!("{{\"name\":{},\"type\":{}}}",
+                                      canonical_string(&field.name),
+                                      canonical_form(&field.value)))
+                .collect();
+            format!("{{\"name\":{},\"type\":\"record\",\"fields\":[{}]}}",
+                    canonical_string(&schema.name), fields.join(","))
+        }
+        AvroType::Enum(ref schema) => {
+            let symbols: Vec<String> = schema.symbols.iter()
+                .map(|symbol| canonical_string(symbol))
+                .collect();
+            format!("{{\"name\":{},\"type\":\"enum\",\"symbols\":[{}]}}",
+                    canonical_string(&schema.name), symbols.join(","))
+        }
+        AvroType::Fixed(ref schema) => {
+            format!("{{\"name\":{},\"type\":\"fixed\",\"size\":{}}}",
+                    canonical_string(&schema.name), schema.size)
+        }
+        AvroType::Array(ref schema) => {
+            format!("{{\"type\":\"array\",\"items\":{}}}", canonical_form(&schema.typ))
+        }
+        AvroType::Map(ref schema) => {
+            format!("{{\"type\":\"map\",\"values\":{}}}", canonical_form(&schema.vtype))
+        }
+        AvroType::Union(ref schema) => {
+            let branches: Vec<String> = schema.types.iter().map(canonical_form).collect();
+            format!("[{}]", branches.join(","))
+        }
+        // The Parsing Canonical Form transformation strips `logicalType`
+        // (and `precision`/`scale`) along with every other attribute
+        // outside its fixed allow-list, so a logical type's canonical
+        // form - and therefore its fingerprint - is just its base type's.
+        AvroType::Logical(_, ref base) => canonical_form(base),
+    }
+}
+
+fn canonical_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// The CRC-64-AVRO "empty" seed: the fingerprint of a zero-length
+/// input, per https://avro.apache.org/docs/current/specification/#schema-fingerprints.
+const FINGERPRINT_EMPTY: u64 = 0xc15d213aa4d7a795;
+
+fn fingerprint_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    for i in 0..256 {
+        let mut fp = i as u64;
+        for _ in 0..8 {
+            fp = (fp >> 1) ^ (FINGERPRINT_EMPTY & (0u64.wrapping_sub(fp & 1)));
+        }
+        table[i] = fp;
+    }
+    table
+}
+
+fn rabin_fingerprint(bytes: &[u8]) -> u64 {
+    let table = fingerprint_table();
+    let mut fp = FINGERPRINT_EMPTY;
+    for &byte in bytes {
+        fp = (fp >> 8) ^ table[((fp ^ byte as u64) & 0xff) as usize];
+    }
+    fp
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Decoder, Encoder, Schema, SchemaError};
+    use super::super::io::Reader;
+    use super::super::values::{AvroArray, AvroDecimal, AvroEnum, AvroMap, AvroRecord, AvroUnion, AvroValue};
+
+    fn roundtrip(avsc: &str, value: &AvroValue) -> AvroValue {
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let bytes = encoder.encode(value).unwrap();
+        let decoder = Decoder::from_avsc(avsc).unwrap();
+        let mut reader = Reader::new(&bytes);
+        decoder.decode(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn test_record_with_non_empty_map_field_leaves_later_fields_intact() {
+        // Regression test: encode_value's Map arm always writes a
+        // trailing 0x0 block terminator after a non-empty map, so
+        // decode_value must consume it too or every field after the
+        // map desyncs.
+        let avsc = r#"{"type":"record","name":"R","fields":[
+            {"name":"m","type":{"type":"map","values":"int"}},
+            {"name":"after","type":"int"}
+        ]}"#;
+
+        let mut entries = HashMap::new();
+        entries.insert(String::from("a"), AvroValue::Int(1));
+        let value = AvroValue::Record(AvroRecord::new(vec![
+            (String::from("m"), AvroValue::Map(AvroMap::new(entries))),
+            (String::from("after"), AvroValue::Int(42)),
+        ]));
+
+        match roundtrip(avsc, &value) {
+            AvroValue::Record(record) => match record.get("after") {
+                Some(&AvroValue::Int(after)) => assert_eq!(after, 42),
+                _ => panic!("expected an Int field"),
+            },
+            _ => panic!("expected a Record"),
+        }
+    }
+
+    #[test]
+    fn test_empty_map_roundtrip() {
+        let avsc = r#"{"type":"map","values":"int"}"#;
+        let value = AvroValue::Map(AvroMap::new(HashMap::new()));
+        match roundtrip(avsc, &value) {
+            AvroValue::Map(map) => assert!(map.get("anything").is_none()),
+            _ => panic!("expected a Map"),
+        }
+    }
+
+    #[test]
+    fn test_array_roundtrip() {
+        let avsc = r#"{"type":"array","items":"string"}"#;
+        let value = AvroValue::Array(AvroArray::new(vec![
+            AvroValue::String(String::from("a")),
+            AvroValue::String(String::from("b")),
+        ]));
+        match roundtrip(avsc, &value) {
+            AvroValue::Array(array) => assert_eq!(array.items().len(), 2),
+            _ => panic!("expected an Array"),
+        }
+    }
+
+    #[test]
+    fn test_enum_roundtrip_and_unknown_symbol_rejected() {
+        let avsc = r#"{"type":"enum","name":"Suit","symbols":["SPADES","HEARTS"]}"#;
+        let value = AvroValue::Enum(AvroEnum::new(String::from("HEARTS")));
+        match roundtrip(avsc, &value) {
+            AvroValue::Enum(e) => assert_eq!(e.symbol(), "HEARTS"),
+            _ => panic!("expected an Enum"),
+        }
+
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let bad = AvroValue::Enum(AvroEnum::new(String::from("CLUBS")));
+        match encoder.encode(&bad) {
+            Err(SchemaError::UnknownSymbol) => (),
+            other => panic!("expected UnknownSymbol, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_union_roundtrip() {
+        let avsc = r#"["null","int"]"#;
+        let value = AvroValue::Union(AvroUnion::new(1, AvroValue::Int(7)));
+        match roundtrip(avsc, &value) {
+            AvroValue::Union(u) => {
+                assert_eq!(u.index(), 1);
+                match u.value() {
+                    &AvroValue::Int(i) => assert_eq!(i, 7),
+                    _ => panic!("expected an Int branch"),
+                }
+            }
+            _ => panic!("expected a Union"),
+        }
+    }
+
+    #[test]
+    fn test_missing_field_falls_back_to_default() {
+        let avsc = r#"{"type":"record","name":"R","fields":[
+            {"name":"count","type":"int","default":9}
+        ]}"#;
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let value = AvroValue::Record(AvroRecord::new(vec![]));
+        let bytes = encoder.encode(&value).unwrap();
+
+        let decoder = Decoder::from_avsc(avsc).unwrap();
+        let mut reader = Reader::new(&bytes);
+        match decoder.decode(&mut reader).unwrap() {
+            AvroValue::Record(record) => match record.get("count") {
+                Some(&AvroValue::Int(count)) => assert_eq!(count, 9),
+                _ => panic!("expected an Int field"),
+            },
+            _ => panic!("expected a Record"),
+        }
+    }
+
+    #[test]
+    fn test_missing_field_without_default_errors() {
+        let avsc = r#"{"type":"record","name":"R","fields":[
+            {"name":"count","type":"int"}
+        ]}"#;
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let value = AvroValue::Record(AvroRecord::new(vec![]));
+        match encoder.encode(&value) {
+            Err(SchemaError::MissingField(ref name)) => assert_eq!(name, "count"),
+            other => panic!("expected MissingField, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_doc_strings_and_field_order_of_equal_schemas() {
+        let a = Schema::from_avsc(r#"{"type":"record","name":"R","fields":[{"name":"x","type":"int"}]}"#).unwrap();
+        let b = Schema::from_avsc(r#"{
+            "name": "R",
+            "type": "record",
+            "doc": "irrelevant commentary",
+            "fields": [ { "name": "x", "type": "int" } ]
+        }"#).unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_differs_for_different_schemas() {
+        let a = Schema::from_avsc(r#"{"type":"record","name":"R","fields":[{"name":"x","type":"int"}]}"#).unwrap();
+        let b = Schema::from_avsc(r#"{"type":"record","name":"R","fields":[{"name":"y","type":"int"}]}"#).unwrap();
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_single_object_roundtrip() {
+        let avsc = r#"{"type":"record","name":"R","fields":[{"name":"x","type":"int"}]}"#;
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let value = AvroValue::Record(AvroRecord::new(vec![(String::from("x"), AvroValue::Int(5))]));
+        let bytes = encoder.encode_single_object(&value).unwrap();
+
+        let decoder = Decoder::from_avsc(avsc).unwrap();
+        let mut reader = Reader::new(&bytes);
+        match decoder.decode_single_object(&mut reader).unwrap() {
+            AvroValue::Record(record) => match record.get("x") {
+                Some(&AvroValue::Int(x)) => assert_eq!(x, 5),
+                _ => panic!("expected an Int field"),
+            },
+            _ => panic!("expected a Record"),
+        }
+    }
+
+    #[test]
+    fn test_single_object_rejects_bad_marker() {
+        let avsc = r#""int""#;
+        let decoder = Decoder::from_avsc(avsc).unwrap();
+        let mut reader = Reader::new(&[0xC3, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0x2]);
+        match decoder.decode_single_object(&mut reader) {
+            Err(SchemaError::BadSingleObjectMarker) => (),
+            Err(other) => panic!("expected BadSingleObjectMarker, got {:?}", other),
+            Ok(_) => panic!("expected BadSingleObjectMarker, got Ok"),
+        }
+    }
+
+    #[test]
+    fn test_single_object_rejects_mismatched_fingerprint() {
+        let avsc_int = r#""int""#;
+        let avsc_string = r#""string""#;
+        let encoder = Encoder::from_avsc(avsc_string).unwrap();
+        let bytes = encoder.encode_single_object(&AvroValue::String(String::from("hi"))).unwrap();
+
+        let decoder = Decoder::from_avsc(avsc_int).unwrap();
+        let mut reader = Reader::new(&bytes);
+        match decoder.decode_single_object(&mut reader) {
+            Err(SchemaError::FingerprintMismatch) => (),
+            Err(other) => panic!("expected FingerprintMismatch, got {:?}", other),
+            Ok(_) => panic!("expected FingerprintMismatch, got Ok"),
+        }
+    }
+
+    #[test]
+    fn test_decimal_bytes_base_roundtrip() {
+        let avsc = r#"{"type":"bytes","logicalType":"decimal","precision":10,"scale":2}"#;
+        let value = AvroValue::Decimal(AvroDecimal::new(-12345, 10, 2));
+        match roundtrip(avsc, &value) {
+            AvroValue::Decimal(d) => {
+                assert_eq!(d.unscaled(), -12345);
+                assert_eq!(d.precision(), 10);
+                assert_eq!(d.scale(), 2);
+            }
+            _ => panic!("expected a Decimal"),
+        }
+    }
+
+    #[test]
+    fn test_decimal_fixed_base_roundtrip() {
+        let avsc = r#"{"type":"fixed","name":"D","size":4,"logicalType":"decimal","precision":9,"scale":1}"#;
+        let value = AvroValue::Decimal(AvroDecimal::new(100, 9, 1));
+        match roundtrip(avsc, &value) {
+            AvroValue::Decimal(d) => assert_eq!(d.unscaled(), 100),
+            _ => panic!("expected a Decimal"),
+        }
+    }
+
+    #[test]
+    fn test_decimal_fixed_base_rejects_values_too_large_for_size() {
+        let avsc = r#"{"type":"fixed","name":"D","size":1,"logicalType":"decimal","precision":2,"scale":0}"#;
+        let encoder = Encoder::from_avsc(avsc).unwrap();
+        let value = AvroValue::Decimal(AvroDecimal::new(1000, 2, 0));
+        match encoder.encode(&value) {
+            Err(SchemaError::TypeMismatch) => (),
+            other => panic!("expected TypeMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_date_timestamp_and_uuid_logical_types_use_base_wire_format() {
+        // Date/TimestampMillis/TimestampMicros/Uuid carry no extra
+        // AvroValue variant - their wire encoding is identical to the
+        // base int/long/string type's.
+        let date = Schema::from_avsc(r#"{"type":"int","logicalType":"date"}"#).unwrap();
+        let plain_int = Schema::from_avsc(r#""int""#).unwrap();
+        assert_eq!(date.fingerprint(), plain_int.fingerprint());
+
+        let timestamp = Encoder::from_avsc(r#"{"type":"long","logicalType":"timestamp-millis"}"#).unwrap();
+        let plain_long = Encoder::from_avsc(r#""long""#).unwrap();
+        assert_eq!(timestamp.encode(&AvroValue::Long(1500)).unwrap(),
+                   plain_long.encode(&AvroValue::Long(1500)).unwrap());
+
+        let avsc = r#"{"type":"string","logicalType":"uuid"}"#;
+        let value = AvroValue::String(String::from("not-really-a-uuid"));
+        match roundtrip(avsc, &value) {
+            AvroValue::String(s) => assert_eq!(s, "not-really-a-uuid"),
+            _ => panic!("expected a String"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_logical_type_falls_back_to_base_primitive() {
+        let avsc = r#"{"type":"int","logicalType":"not-a-real-type"}"#;
+        let value = AvroValue::Int(3);
+        match roundtrip(avsc, &value) {
+            AvroValue::Int(i) => assert_eq!(i, 3),
+            _ => panic!("expected an Int"),
+        }
+    }
+}
@@ -4,7 +4,7 @@
 //
 // Avro Value types for ad-hoc data
 
-use super::codec::{AvroCodec, ByteStream};
+use std::collections::HashMap;
 
 pub enum AvroValue {
     Null,
@@ -21,24 +21,21 @@ pub enum AvroValue {
     Array(AvroArray),
     Map(AvroMap),
     Union(AvroUnion),
+    Decimal(AvroDecimal),
 }
 
 pub struct AvroRecord {
-
+    pub(crate) fields: Vec<(String, AvroValue)>,
 }
 
 impl AvroRecord {
+    pub fn new(fields: Vec<(String, AvroValue)>) -> AvroRecord {
+        AvroRecord { fields: fields }
+    }
 
-}
-
-impl AvroCodec for AvroRecord {
-   fn encode(&self) -> Vec<u8> {
-        unimplemented!();
-   }
-
-   fn decode(bytes: &mut ByteStream) -> Option<Self> {
-        unimplemented!();
-   }
+    pub fn get(&self, name: &str) -> Option<&AvroValue> {
+        self.fields.iter().find(|entry| entry.0 == name).map(|entry| &entry.1)
+    }
 }
 
 pub struct AvroFixed {
@@ -81,17 +78,93 @@ impl AvroFixed {
 }
 
 pub struct AvroEnum {
+    pub(crate) symbol: String,
+}
+
+impl AvroEnum {
+    pub fn new(symbol: String) -> AvroEnum {
+        AvroEnum { symbol: symbol }
+    }
 
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
 }
 
 pub struct AvroArray {
+    pub(crate) items: Vec<AvroValue>,
+}
 
+impl AvroArray {
+    pub fn new(items: Vec<AvroValue>) -> AvroArray {
+        AvroArray { items: items }
+    }
+
+    pub fn items(&self) -> &[AvroValue] {
+        &self.items
+    }
 }
 
 pub struct AvroMap {
+    pub(crate) entries: HashMap<String, AvroValue>,
+}
+
+impl AvroMap {
+    pub fn new(entries: HashMap<String, AvroValue>) -> AvroMap {
+        AvroMap { entries: entries }
+    }
 
+    pub fn get(&self, key: &str) -> Option<&AvroValue> {
+        self.entries.get(key)
+    }
 }
 
+/// A resolved union value: the index of the branch in the schema's
+/// declared type list, and the decoded value of that branch.
 pub struct AvroUnion {
+    pub(crate) index: i32,
+    pub(crate) value: Box<AvroValue>,
+}
+
+impl AvroUnion {
+    pub fn new(index: i32, value: AvroValue) -> AvroUnion {
+        AvroUnion { index: index, value: Box::new(value) }
+    }
+
+    pub fn index(&self) -> i32 {
+        self.index
+    }
 
+    pub fn value(&self) -> &AvroValue {
+        &self.value
+    }
+}
+
+/// A decoded `decimal` logical value: the unscaled integer and the
+/// `precision`/`scale` carried over from the schema. Held as an `i64`
+/// rather than an arbitrary-precision integer, since this crate has no
+/// bignum dependency to reach for - decimals whose unscaled value
+/// doesn't fit in 64 bits aren't supported.
+pub struct AvroDecimal {
+    pub(crate) unscaled: i64,
+    pub(crate) precision: usize,
+    pub(crate) scale: usize,
+}
+
+impl AvroDecimal {
+    pub fn new(unscaled: i64, precision: usize, scale: usize) -> AvroDecimal {
+        AvroDecimal { unscaled: unscaled, precision: precision, scale: scale }
+    }
+
+    pub fn unscaled(&self) -> i64 {
+        self.unscaled
+    }
+
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
 }